@@ -4,32 +4,154 @@ use iced::{
     executor,
     keyboard::{KeyCode, Modifiers},
     pure::{Application, Element},
-    Command,
+    time, Command, Subscription,
 };
 use iced_native::{event::Status, keyboard::Event, subscription::events_with};
 use log::info;
-use std::{fs::Metadata, ops::Index, path::PathBuf};
-
-use tokio::fs::remove_file;
+use std::{
+    borrow::Cow,
+    ffi::OsString,
+    fs::Metadata,
+    ops::Index,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
 
 use crate::{
-    mode::Mode,
-    tasks::get_files,
+    bulk_rename::bulk_rename,
+    command::{self, History},
+    details::{self, Detail, Owners},
+    frecency::{self, Frecency, ImportError, ImportSource},
+    input::{Edit, LineInput},
+    jobs::{Job, JobContext, JobId, JobKind, Progress},
+    journal::{Direction, Journal, Operation, Reverted},
+    jumps::Jumplist,
+    keymap::{Chord, Counted, Keymap, Pending, Section, SEQUENCE_TIMEOUT},
+    marks::Marks,
+    mode::{Mode, SearchMode},
+    notification::{NotificationId, Notifications, Severity},
+    preview::{self, Preview},
+    sort::{Sort, SortKey, Sorts},
+    tasks::{
+        count_conflicts, count_items, create_entry, get_files, missing_dirs, rename_file,
+        ConflictPolicy, CreateKind, Filter,
+    },
     theme::{RatioExt, Theme},
+    trash::{self, TrashEntry},
     ui::{self},
+    watch::{self, Change},
 };
 
+/// How often notifications are checked for having been up long enough.
+const EXPIRY_INTERVAL: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Clone)]
 pub enum Message {
-    FilesLoaded(Vec<File>),
+    /// A listing of a directory, along with whatever couldn't be read.
+    FilesLoaded(PathBuf, Vec<File>, Vec<ListError>),
     KeyEvent(Event),
-    FileDeleteResult(Result<(), FileDeleteError>),
+    /// No key came in for a while after the key sequence with this generation.
+    KeyTimeout(usize),
+    /// Files in the watched directory changed behind our back.
+    Watched(PathBuf, Vec<Change>),
+    PreviewLoaded(PathBuf, Preview),
+    /// Jumplist directories that no longer exist, checked before jumping from this directory.
+    JumpChecked(PathBuf, Action, usize, Vec<PathBuf>),
+    /// Files and bytes counted for the delete with this id, started in this directory.
+    DeleteCounted(usize, PathBuf, usize, u64),
+    FileDeleteResult(Vec<Operation>, Vec<FileDeleteError>),
+    /// How many files already in the way of a paste into this directory.
+    PasteConflicts(PathBuf, usize),
+    Pasted(Vec<Operation>, Vec<PasteError>),
+    Renamed(Result<Operation, RenameError>),
+    Created(Result<(Box<File>, Operation), CreateError>),
+    /// Command line a completion was started from, and what it completed to.
+    Completed(String, String),
+    HistoryLoaded(History, Option<LoadError>),
+    SortsLoaded(Sorts, Option<LoadError>),
+    MarksLoaded(Marks, Option<LoadError>),
+    FrecencyLoaded(Frecency, Option<LoadError>),
+    Imported(Result<Frecency, ImportError>),
+    /// The user's keymap, along with anything wrong with it.
+    KeymapLoaded(Keymap, Vec<String>),
+    Saved(Result<(), SaveError>),
+    BulkRenamed(Vec<Operation>, Result<(), BulkRenameError>),
+    Reverted(Direction, Reverted),
+    TrashLoaded(Vec<TrashEntry>),
+    TrashResult(Result<(), TrashError>),
+    DismissNotification(NotificationId),
+    ExpireNotifications(Instant),
+    JobProgress(JobId, Progress),
+    /// A job is done, with the message carrying its result.
+    JobFinished(JobId, Box<Message>),
     ColorInput(SettingsInputKind, String),
     SubmitColor(SettingsInputKind),
 }
 
+/// A directory, or an entry in one, that couldn't be listed.
+#[derive(Debug, Clone)]
+pub struct ListError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileDeleteError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PasteError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BulkRenameError(pub String);
+
+/// Failure to write one of the files fls keeps its state in.
+#[derive(Debug, Clone)]
+pub struct SaveError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Failure to read one of the files fls keeps its state in.
+#[derive(Debug, Clone)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrashError(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Yank,
+    Cut,
+}
+
+/// Files yanked or cut, waiting to be pasted. Outlives directory changes.
 #[derive(Debug, Clone)]
-pub struct FileDeleteError(pub PathBuf);
+pub struct Register {
+    pub kind: RegisterKind,
+    pub paths: Vec<PathBuf>,
+}
 
 #[derive(Debug)]
 pub struct DisplayedFile {
@@ -46,10 +168,6 @@ impl Files {
         Self(Vec::new())
     }
 
-    pub fn remove(&mut self, index: usize) -> DisplayedFile {
-        self.0.remove(index)
-    }
-
     pub fn drain(&mut self, p: impl Fn(&DisplayedFile) -> bool) -> Vec<DisplayedFile> {
         let mut vec = Vec::new();
         let mut i = 0;
@@ -77,6 +195,28 @@ impl Files {
         self.0 = files;
     }
 
+    /// Replaces the file at the same path, keeping whether it is selected, or adds it.
+    pub fn update(&mut self, file: File) {
+        match self.0.iter_mut().find(|f| f.data.path == file.path) {
+            Some(existing) => existing.data = file,
+            None => self.0.push(file.into()),
+        }
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.0.retain(|f| f.data.path != path);
+    }
+
+    /// Orders the files by `sort`, or by how well they match the search when `by_score` is set.
+    pub(super) fn sort(&mut self, sort: Sort, by_score: bool) {
+        self.0.sort_by(|a, b| sort.compare(&a.data, &b.data));
+
+        // Stable, so equally good matches stay in sort order
+        if by_score {
+            self.0.sort_by_key(|f| std::cmp::Reverse(f.curr_score));
+        }
+    }
+
     pub(super) fn new_scores(&mut self, score_fn: impl Fn(&File) -> i64) {
         self.0
             .iter_mut()
@@ -96,15 +236,98 @@ impl Index<usize> for Files {
     }
 }
 
-pub struct Fls {
+/// A directory being browsed, along with everything tied to it.
+#[derive(Debug)]
+pub struct Tab {
     pub current_dir: PathBuf,
     pub mode: Mode,
     pub search_term: String,
     pub hovered: usize,
+    /// What couldn't be read in the last listing of `current_dir`.
+    pub list_errors: Vec<ListError>,
+    cache: Files,
+    /// How many levels of subdirectories `cache` currently holds.
+    loaded_depth: usize,
+    /// File to hover once the listing of `current_dir` has loaded.
+    pending_hover: Option<PathBuf>,
+    jumps: Jumplist,
+    /// Files selected before visual mode started, kept selected whatever the range covers.
+    visual_base: Vec<PathBuf>,
+    /// Files waiting for the user to confirm their deletion.
+    pending_delete: Vec<PathBuf>,
+    /// What is being pasted where, once conflicts with what is already there are resolved.
+    pending_paste: Option<(Register, PathBuf)>,
+}
+
+impl Tab {
+    pub fn new(current_dir: PathBuf) -> Self {
+        Self {
+            current_dir,
+            mode: Mode::Normal,
+            search_term: String::new(),
+            hovered: 0,
+            list_errors: Vec::new(),
+            cache: Files::new(),
+            loaded_depth: 0,
+            pending_hover: None,
+            jumps: Jumplist::default(),
+            visual_base: Vec::new(),
+            pending_delete: Vec::new(),
+            pending_paste: None,
+        }
+    }
+
+    /// Name shown in the tab strip.
+    pub fn title(&self) -> Cow<'_, str> {
+        match self.current_dir.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => self.current_dir.to_string_lossy(),
+        }
+    }
+}
+
+pub struct Fls {
+    /// The tab that is shown.
+    pub tab: Tab,
+    /// Every other tab, in order. `tab` sits between them at `active_tab`.
+    other_tabs: Vec<Tab>,
+    pub active_tab: usize,
     pub should_exit: bool,
     pub curr_view: View,
     pub theme: Theme,
-    cache: Files,
+    /// Errors and other things to tell the user, shown as toasts.
+    pub notifications: Notifications,
+    pub register: Option<Register>,
+    /// Which files are listed, shown in the status bar when it differs from the default.
+    pub filter: Filter,
+    pub show_preview: bool,
+    /// Whether `details` are shown next to the names.
+    pub long_listing: bool,
+    pub details: Vec<Detail>,
+    /// Names of the owners of listed files.
+    pub owners: Owners,
+    /// Last preview loaded, along with the file it is of. Stale once another file is hovered.
+    pub preview: Option<(PathBuf, Preview)>,
+    /// Text being typed into the current prompt, e.g. the new name while renaming.
+    pub input: LineInput,
+    /// Directories saved under a letter, listed in the bookmarks view.
+    pub marks: Marks,
+    /// Visited directories, jumped to from the jump prompt.
+    pub frecency: Frecency,
+    history: History,
+    /// What keys do, also used to show which keys to press in prompts.
+    pub keymap: Keymap,
+    sorts: Sorts,
+    /// Start of a key sequence, or a count, typed in the main view.
+    pub pending_keys: Pending,
+    /// Set when the next `CharacterReceived` belongs to the key that opened a prompt.
+    swallow_char: bool,
+    /// Background jobs still running, oldest first.
+    pub jobs: Vec<Job>,
+    next_job_id: JobId,
+    journal: Journal,
+    /// Bumped with every delete, so a count that comes in after another one started is dropped.
+    delete_id: usize,
 }
 
 impl Application for Fls {
@@ -123,33 +346,322 @@ impl Application for Fls {
         let mut command = Command::none();
 
         match message {
-            Message::FilesLoaded(f) => self.cache.set(f.into_iter().map(Into::into).collect()),
+            // Listed for a tab that has been left since, it is listed again once it is shown
+            Message::FilesLoaded(dir, ..) if dir != self.tab.current_dir => (),
+            Message::FilesLoaded(_, f, errors) => {
+                self.tab.list_errors = errors;
+                self.owners.learn(&f);
+                self.tab.cache.set(f.into_iter().map(Into::into).collect());
+                self.refresh_filter();
+
+                if let Some(path) = self.tab.pending_hover.take() {
+                    let idx = self.files().position(|f| f.data.path == path);
+                    self.tab.hovered = idx.unwrap_or(self.tab.hovered);
+                }
+            }
+            Message::JumpChecked(dir, ..) if dir != self.tab.current_dir => (),
+            Message::JumpChecked(_, direction, steps, missing) => {
+                self.tab.jumps.forget(&missing);
+                command = self.step_jumps(direction, steps);
+            }
             Message::KeyEvent(e) => {
-                let action = match self.curr_view {
-                    View::MainView => self.mode.parse_event(e),
-                    View::Settings { .. } => View::parse_settings(e),
+                match e {
+                    Event::KeyPressed { .. } => self.swallow_char = false,
+                    Event::CharacterReceived(_) if self.swallow_char => {
+                        self.swallow_char = false;
+                        return command;
+                    }
+                    _ => (),
+                }
+
+                let mode = self.tab.mode;
+                let was_bookmarks = matches!(self.curr_view, View::Bookmarks(_));
+                let pressed = matches!(e, Event::KeyPressed { .. });
+                let actions = match self.curr_view {
+                    View::MainView => {
+                        self.tab
+                            .mode
+                            .parse_event(e, &self.keymap, &mut self.pending_keys)
+                    }
+                    View::Settings { .. } => vec![(View::parse_settings(e), None)],
+                    View::Trash(_) => match e {
+                        Event::KeyPressed {
+                            key_code,
+                            modifiers,
+                        } => self.keymap.feed(
+                            Section::Trash,
+                            &mut self.pending_keys,
+                            Chord::new(key_code, modifiers),
+                        ),
+                        _ => vec![],
+                    },
+                    View::Bookmarks(ref b) => vec![(View::parse_bookmarks(b, e), None)],
                 };
 
-                command = self.take_action(action);
+                let mut commands: Vec<_> = actions
+                    .into_iter()
+                    .map(|counted| self.take_counted(counted))
+                    .collect();
+
+                if self.pending_keys.is_waiting() {
+                    let generation = self.pending_keys.generation;
+                    commands.push(Command::perform(sleep(SEQUENCE_TIMEOUT), move |_| {
+                        Message::KeyTimeout(generation)
+                    }));
+                }
+
+                command = Command::batch(commands);
+
+                // The key that opened a prompt is followed by its character, which shouldn't end
+                // up in the prompt
+                let opened_prompt = self.tab.mode != mode
+                    && matches!(
+                        self.tab.mode,
+                        Mode::Rename
+                            | Mode::Create(_)
+                            | Mode::Command
+                            | Mode::SetMark
+                            | Mode::GoToMark
+                            | Mode::Jump
+                    );
+                let opened_bookmarks =
+                    !was_bookmarks && matches!(self.curr_view, View::Bookmarks(_));
+                if opened_prompt || opened_bookmarks {
+                    self.swallow_char = pressed;
+                }
             }
-            Message::FileDeleteResult(r) => r.unwrap(),
-            Message::SubmitColor(id) => match &mut self.curr_view {
-                View::Settings(s) => match id {
-                    SettingsInputKind::PrimaryColor => {
-                        self.theme.primary = Rgb::from_hex_str(&s.primary_input)
-                            .unwrap()
-                            .as_ratio()
-                            .to_color()
+            Message::KeyTimeout(generation) => {
+                if generation == self.pending_keys.generation {
+                    let section = self.tab.mode.section();
+                    if let Some(counted) = self.keymap.timeout(section, &mut self.pending_keys) {
+                        command = self.take_counted(counted);
                     }
-                    SettingsInputKind::SecondaryColor => {
-                        self.theme.secondary = Rgb::from_hex_str(&s.secondary_input)
-                            .unwrap()
-                            .as_ratio()
-                            .to_color()
+                }
+            }
+            Message::Watched(dir, _) if dir != self.tab.current_dir => (),
+            Message::Watched(_, changes) => {
+                let hovered = self
+                    .files()
+                    .nth(self.tab.hovered)
+                    .map(|f| f.data.path.clone());
+
+                for change in changes {
+                    // The preview of a file that changed is outdated
+                    let path = match &change {
+                        Change::Changed(file) => &file.path,
+                        Change::Removed(path) => path,
+                    };
+                    if matches!(&self.preview, Some((previewed, _)) if previewed == path) {
+                        self.preview = None;
                     }
-                },
-                _ => unreachable!(),
-            },
+
+                    match change {
+                        Change::Changed(file) => {
+                            self.owners.learn([&*file]);
+                            self.tab.cache.update(*file);
+                        }
+                        Change::Removed(path) => self.tab.cache.remove(&path),
+                    }
+                }
+                self.rescore();
+
+                // Stay on the same file, or where it was if it went away
+                let count = self.files().count();
+                let idx = hovered.and_then(|path| self.files().position(|f| f.data.path == path));
+                self.tab.hovered =
+                    idx.unwrap_or_else(|| self.tab.hovered.min(count.saturating_sub(1)));
+            }
+            Message::PreviewLoaded(path, preview) => self.preview = Some((path, preview)),
+            Message::DeleteCounted(id, dir, items, bytes) => {
+                // Navigating or switching tabs while counting drops the delete
+                let current = id == self.delete_id && dir == self.tab.current_dir;
+                if current && !self.tab.pending_delete.is_empty() {
+                    self.tab.mode = Mode::ConfirmDelete { items, bytes };
+                }
+            }
+            Message::FileDeleteResult(ops, errors) => {
+                self.journal.record(ops);
+
+                if !errors.is_empty() {
+                    for FileDeleteError { path, reason } in errors {
+                        self.notify(
+                            Severity::Error,
+                            format!("Failed to delete {}: {reason}", path.display()),
+                        );
+                    }
+
+                    // Bring back whatever couldn't be deleted
+                    command = self.reload();
+                }
+            }
+            // The paste was given up on while counting, e.g. by switching tabs
+            Message::PasteConflicts(dest, _)
+                if self.tab.pending_paste.as_ref().map(|(_, d)| d) != Some(&dest) => {}
+            Message::PasteConflicts(_, 0) => command = self.paste(ConflictPolicy::Skip),
+            Message::PasteConflicts(_, conflicts) => {
+                self.tab.mode = Mode::ResolveConflict { conflicts }
+            }
+            Message::Pasted(ops, errors) => {
+                self.journal.record(ops);
+
+                for PasteError { path, reason } in errors {
+                    self.notify(
+                        Severity::Error,
+                        format!("Failed to paste {}: {reason}", path.display()),
+                    );
+                }
+
+                command = self.reload();
+            }
+            Message::Renamed(Ok(op)) => {
+                if let Operation::Renamed { to, .. } = &op {
+                    self.tab.pending_hover = Some(to.clone());
+                }
+
+                self.journal.record(vec![op]);
+                command = self.reload();
+            }
+            Message::Renamed(Err(RenameError { path, reason })) => self.notify(
+                Severity::Error,
+                format!("Failed to rename {}: {reason}", path.display()),
+            ),
+            Message::Created(Ok((file, op))) => {
+                self.journal.record(vec![op]);
+
+                if file.parent != self.tab.current_dir {
+                    return command;
+                }
+
+                // The watcher may have picked it up already
+                let path = file.path.clone();
+                self.tab.cache.update(*file);
+                self.refresh_filter();
+
+                let idx = self.files().position(|f| f.data.path == path);
+                self.tab.hovered = idx.unwrap_or(self.tab.hovered);
+            }
+            Message::Created(Err(CreateError { path, reason })) => self.notify(
+                Severity::Error,
+                format!("Failed to create {}: {reason}", path.display()),
+            ),
+            Message::Completed(line, completed) => {
+                // Typing went on while completing, the completion is stale
+                if self.tab.mode == Mode::Command && self.input.text == line {
+                    let len = completed.len();
+                    self.input = LineInput::new(completed, len);
+                }
+            }
+            Message::HistoryLoaded(history, error) => {
+                self.history.extend_front(history);
+                self.load_failed(error);
+            }
+            Message::SortsLoaded(sorts, error) => {
+                self.sorts = sorts;
+                self.refresh_filter();
+                self.load_failed(error);
+            }
+            Message::MarksLoaded(marks, error) => {
+                self.marks = marks;
+                self.load_failed(error);
+            }
+            Message::FrecencyLoaded(loaded, error) => {
+                // Directories visited while loading count too
+                let visited = std::mem::replace(&mut self.frecency, loaded);
+                self.frecency.merge(visited);
+                self.load_failed(error);
+            }
+            Message::Imported(Ok(imported)) => {
+                let count = imported.len();
+                self.frecency.merge(imported);
+                // The load already failed loudly, this only says what it means for the import
+                if self.frecency.is_unreadable() {
+                    self.notify(
+                        Severity::Warning,
+                        format!(
+                            "Imported {count} directories for this session only, the visited \
+                             directories couldn't be read so they aren't saved"
+                        ),
+                    );
+                } else {
+                    self.notify(Severity::Info, format!("Imported {count} directories"));
+                }
+                command = Command::perform(self.frecency.save(), Message::Saved);
+            }
+            Message::Imported(Err(ImportError { path, reason })) => self.notify(
+                Severity::Error,
+                format!("Failed to import {}: {reason}", path.display()),
+            ),
+            Message::KeymapLoaded(keymap, errors) => {
+                self.keymap = keymap;
+
+                for e in errors {
+                    self.notify(Severity::Warning, format!("Keymap: {e}"));
+                }
+            }
+            Message::Saved(Ok(())) => (),
+            Message::Saved(Err(SaveError { path, reason })) => self.notify(
+                Severity::Error,
+                format!("Failed to save {}: {reason}", path.display()),
+            ),
+            Message::BulkRenamed(ops, result) => {
+                self.journal.record(ops);
+
+                if let Err(BulkRenameError(e)) = result {
+                    self.notify(Severity::Error, format!("Bulk rename: {e}"));
+                }
+
+                command = self.reload();
+            }
+            Message::Reverted(direction, reverted) => {
+                if let Some(e) = &reverted.error {
+                    self.notify(Severity::Error, format!("{direction:?} failed: {e}"));
+                }
+
+                self.journal.finish(direction, reverted);
+                command = self.reload();
+            }
+            Message::TrashLoaded(entries) => {
+                if let View::Trash(t) = &mut self.curr_view {
+                    t.hovered = t.hovered.min(entries.len().saturating_sub(1));
+                    t.entries = entries;
+                }
+            }
+            Message::TrashResult(Ok(())) => command = Command::batch([load_trash(), self.reload()]),
+            Message::TrashResult(Err(TrashError(e))) => {
+                self.notify(Severity::Error, format!("Trash: {e}"))
+            }
+            Message::DismissNotification(id) => self.notifications.dismiss(id),
+            Message::ExpireNotifications(now) => self.notifications.expire(now),
+            Message::JobProgress(id, progress) => {
+                if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+                    job.progress = progress;
+                }
+            }
+            Message::JobFinished(id, message) => {
+                self.jobs.retain(|j| j.id != id);
+                command = self.update(*message);
+            }
+            Message::SubmitColor(id) => {
+                let input = match (&self.curr_view, id) {
+                    (View::Settings(s), SettingsInputKind::PrimaryColor) => &s.primary_input,
+                    (View::Settings(s), SettingsInputKind::SecondaryColor) => &s.secondary_input,
+                    _ => unreachable!(),
+                };
+
+                match Rgb::from_hex_str(input) {
+                    Ok(rgb) => {
+                        let color = rgb.as_ratio().to_color();
+                        match id {
+                            SettingsInputKind::PrimaryColor => self.theme.primary = color,
+                            SettingsInputKind::SecondaryColor => self.theme.secondary = color,
+                        }
+                    }
+                    Err(_) => {
+                        let message = format!("{input:?} is not a hex color");
+                        self.notify(Severity::Warning, message);
+                    }
+                }
+            }
             Message::ColorInput(id, string) => match &mut self.curr_view {
                 View::Settings(s) => match id {
                     SettingsInputKind::PrimaryColor => s.primary_input = string,
@@ -162,12 +674,40 @@ impl Application for Fls {
     }
 
     fn subscription(&self) -> iced::Subscription<Self::Message> {
-        events_with(|e, s| match e {
+        let events = events_with(|e, s| match e {
             iced_native::Event::Keyboard(e) if s == Status::Ignored => Some(Message::KeyEvent(e)),
             // TODO: Could be useful for drag and drop files? idk
             // Event::Window()
             _ => None,
-        })
+        });
+
+        let watcher = watch::watch(self.tab.current_dir.clone(), self.filter);
+
+        // Only ticks while there is something to expire
+        let expiry = if self.notifications.is_empty() {
+            Subscription::none()
+        } else {
+            time::every(EXPIRY_INTERVAL).map(Message::ExpireNotifications)
+        };
+
+        // Loads until the preview is of the hovered file
+        let hovered = self.files().nth(self.tab.hovered).map(|f| &f.data.path);
+        let preview = match (&self.curr_view, hovered, &self.preview) {
+            (View::MainView, Some(hovered), Some((previewed, _))) if previewed == hovered => {
+                Subscription::none()
+            }
+            (View::MainView, Some(hovered), _) if self.show_preview => {
+                let (sort, _) = self.sort();
+                preview::preview(hovered.clone(), self.filter, sort)
+            }
+            _ => Subscription::none(),
+        };
+
+        Subscription::batch(
+            [events, watcher, expiry, preview]
+                .into_iter()
+                .chain(self.jobs.iter().map(Job::subscription)),
+        )
     }
 
     fn view(&self) -> Element<'_, Message, iced::Renderer<Theme>> {
@@ -176,22 +716,58 @@ impl Application for Fls {
 
     fn new(flags: PathBuf) -> (Self, Command<Message>) {
         let app = Fls {
-            cache: Files::new(),
-            current_dir: flags,
-            mode: Mode::Normal,
-            search_term: String::new(),
-            hovered: 0,
+            tab: Tab::new(flags),
+            other_tabs: Vec::new(),
+            active_tab: 0,
             // pane: state,
             // mode: Mode::Normal,
             should_exit: false,
             curr_view: View::MainView,
             theme: Theme::default(),
+            notifications: Notifications::default(),
+            register: None,
+            filter: Filter::default(),
+            show_preview: false,
+            long_listing: false,
+            details: Detail::ALL.to_vec(),
+            owners: Owners::default(),
+            preview: None,
+            input: LineInput::default(),
+            swallow_char: false,
+            history: History::default(),
+            keymap: Keymap::default(),
+            sorts: Sorts::default(),
+            marks: Marks::default(),
+            frecency: Frecency::default(),
+            pending_keys: Pending::default(),
+            jobs: Vec::new(),
+            next_job_id: 0,
+            journal: Journal::default(),
+            delete_id: 0,
         };
 
-        let dir = app.current_dir.clone();
+        let dir = app.tab.current_dir.clone();
+        let filter = app.filter;
         (
             app,
-            Command::perform(get_files(dir), |f| Message::FilesLoaded(f)),
+            Command::batch([
+                list(dir, 0, filter),
+                Command::perform(History::load(), |(history, error)| {
+                    Message::HistoryLoaded(history, error)
+                }),
+                Command::perform(Sorts::load(), |(sorts, error)| {
+                    Message::SortsLoaded(sorts, error)
+                }),
+                Command::perform(Marks::load(), |(marks, error)| {
+                    Message::MarksLoaded(marks, error)
+                }),
+                Command::perform(Frecency::load(), |(frecency, error)| {
+                    Message::FrecencyLoaded(frecency, error)
+                }),
+                Command::perform(Keymap::load(), |(keymap, errors)| {
+                    Message::KeymapLoaded(keymap, errors)
+                }),
+            ]),
         )
     }
 
@@ -210,11 +786,11 @@ impl Application for Fls {
 
 impl Fls {
     pub fn files(&self) -> impl Iterator<Item = &DisplayedFile> {
-        self.cache.files()
+        self.tab.cache.files()
     }
 
     pub fn files_mut(&mut self) -> impl Iterator<Item = &mut DisplayedFile> {
-        self.cache.files_mut()
+        self.tab.cache.files_mut()
     }
 
     fn take_action(&mut self, action: Action) -> Command<Message> {
@@ -222,125 +798,983 @@ impl Fls {
 
         match action {
             Action::Quit => self.should_exit = true,
-            Action::Up => {
-                self.hovered = self.hovered.saturating_sub(1);
-            }
-            Action::Down => {
-                self.hovered = self
-                    .hovered
-                    .saturating_add(1)
-                    .min(self.files().count().saturating_sub(1));
-            }
+            Action::Up => match &mut self.curr_view {
+                View::Trash(t) => t.hovered = t.hovered.saturating_sub(1),
+                View::Bookmarks(b) => b.hovered = b.hovered.saturating_sub(1),
+                _ => self.tab.hovered = self.tab.hovered.saturating_sub(1),
+            },
+            Action::Down => match &mut self.curr_view {
+                View::Trash(t) => {
+                    t.hovered = t
+                        .hovered
+                        .saturating_add(1)
+                        .min(t.entries.len().saturating_sub(1))
+                }
+                View::Bookmarks(b) => {
+                    let count = self.marks.matching(&b.query.text).len();
+                    b.hovered = b.hovered.saturating_add(1).min(count.saturating_sub(1));
+                }
+                _ => {
+                    self.tab.hovered = self
+                        .tab
+                        .hovered
+                        .saturating_add(1)
+                        .min(self.files().count().saturating_sub(1));
+                }
+            },
             Action::NewMode(m) => {
+                let filtered =
+                    matches!(self.tab.mode, Mode::Search(_)) || !self.tab.search_term.is_empty();
+
                 match m {
-                    Mode::Normal => self.search_term.clear(),
+                    Mode::Normal => {
+                        self.tab.search_term.clear();
+
+                        // Leaving visual mode without committing drops the range
+                        if let Mode::Visual { .. } = self.tab.mode {
+                            let base = std::mem::take(&mut self.tab.visual_base);
+                            self.tab
+                                .cache
+                                .files_mut()
+                                .for_each(|f| f.selected = base.contains(&f.data.path));
+                        }
+                    }
+                    Mode::Create(_) | Mode::Jump => self.input = LineInput::default(),
+                    Mode::Command => {
+                        self.input = LineInput::default();
+                        self.history.reset();
+                    }
+                    Mode::Search(SearchMode::Global(depth)) if depth > self.tab.loaded_depth => {
+                        self.tab.loaded_depth = depth;
+                        command = list(self.tab.current_dir.clone(), depth, self.filter);
+                    }
                     _ => (),
                 }
 
-                self.mode = m;
-                self.refresh_filter();
+                self.tab.mode = m;
+
+                // Only searching changes what is listed, prompts and the like keep the hovered
+                // file where it was
+                if filtered || matches!(m, Mode::Search(_)) {
+                    self.refresh_filter();
+                }
+            }
+            Action::DeeperSearch | Action::ShallowerSearch => {
+                if let Mode::Search(SearchMode::Global(depth)) = self.tab.mode {
+                    let depth = match action {
+                        Action::DeeperSearch => depth + 1,
+                        _ => depth.saturating_sub(1).max(1),
+                    };
+
+                    command =
+                        self.take_action(Action::NewMode(Mode::Search(SearchMode::Global(depth))));
+                }
+            }
+            Action::Commit => {
+                let action = match self.tab.mode {
+                    Mode::Rename => Action::CommitRename,
+                    Mode::Create(kind) => Action::CommitCreate(kind),
+                    Mode::Command => Action::RunCommand,
+                    Mode::Jump => {
+                        self.tab.mode = Mode::Normal;
+                        Action::JumpTo(std::mem::take(&mut self.input).text)
+                    }
+                    Mode::Visual { .. } => {
+                        self.end_visual();
+                        Action::None
+                    }
+                    _ => Action::None,
+                };
+
+                command = self.take_action(action);
             }
             Action::ToggleCurrent => {
-                let hovered = self.hovered;
+                let hovered = self.tab.hovered;
                 let _ = self.files_mut().skip(hovered).next().map(|f| {
                     f.selected = !f.selected;
                 });
             }
+            Action::VisualMode => {
+                self.tab.visual_base = self
+                    .files()
+                    .filter(|f| f.selected)
+                    .map(|f| f.data.path.clone())
+                    .collect();
+                self.tab.mode = Mode::Visual {
+                    anchor: self.tab.hovered,
+                };
+            }
+            Action::InvertSelection => self.files_mut().for_each(|f| f.selected = !f.selected),
+            Action::SelectAll => self.files_mut().for_each(|f| f.selected = true),
             Action::Delete => {
-                // TODO: Double clone is unideal but closures make rust stuped
-                let create_command = |path: PathBuf| {
-                    Command::perform(remove_file(path.clone()), move |r| {
-                        let clone = path.clone();
-                        Message::FileDeleteResult(r.map_err(|_| FileDeleteError(clone)))
-                    })
+                self.end_visual();
+                command = self.delete(self.targets());
+            }
+            Action::Top => self.tab.hovered = 0,
+            Action::Bottom => self.tab.hovered = self.files().count().saturating_sub(1),
+            Action::ConfirmDelete => {
+                self.tab.mode = Mode::Normal;
+                command = self.delete_pending();
+            }
+            Action::CancelDelete => {
+                self.tab.mode = Mode::Normal;
+                self.tab.pending_delete.clear();
+            }
+            Action::Yank | Action::Cut => {
+                let kind = match action {
+                    Action::Yank => RegisterKind::Yank,
+                    _ => RegisterKind::Cut,
                 };
 
-                //todo prob deletes once that shouldnt be
-                if self.files().any(|f| f.selected) {
-                    let files = self.cache.drain(|f| f.selected);
-                    command = Command::batch(files.into_iter().map(|f| create_command(f.data.path)))
-                } else {
-                    let file = self.cache.remove(self.hovered);
-
-                    command = create_command(file.data.path)
+                self.end_visual();
+                let paths = self.targets();
+                if !paths.is_empty() {
+                    self.register = Some(Register { kind, paths });
+                    self.files_mut().for_each(|f| f.selected = false);
                 }
-
-                let count = self.files().count() - 1;
-
-                self.hovered = if self.hovered < count {
-                    self.hovered
-                } else {
-                    count
+            }
+            Action::Paste => {
+                if let Some(register) = self.register.clone() {
+                    command = self.paste_into(register, self.tab.current_dir.clone());
                 }
             }
+            Action::PasteWith(policy) => {
+                self.tab.mode = Mode::Normal;
+                command = self.paste(policy);
+            }
             Action::Open => {
-                let file = &self.cache[self.hovered];
-                let path = &file.data.path;
-                if file.data.metadata.is_dir() {
-                    let (new, c) = Self::new(path.clone());
+                let file = match self.files().nth(self.tab.hovered) {
+                    Some(file) => &file.data,
+                    None => return command,
+                };
 
-                    *self = new;
-                    command = c;
-                } else {
-                    open::that(&path).unwrap();
+                let path = file.path.clone();
+                if file.depth > 0 {
+                    // Global search result, jump to where it lives
+                    command = self.change_dir(file.parent.clone());
+                    self.tab.pending_hover = Some(path);
+                } else if file.metadata.is_dir() {
+                    command = self.change_dir(path);
+                } else if let Err(e) = open::that(&path) {
+                    self.notify(
+                        Severity::Error,
+                        format!("Failed to open {}: {e}", path.display()),
+                    );
                 }
             }
-            Action::UpDir => {
-                let (new, c) = Self::new(self.current_dir.parent().unwrap().into());
+            Action::UpDir => match self.tab.current_dir.parent() {
+                Some(parent) => {
+                    let child = self.tab.current_dir.clone();
+                    command = self.change_dir(parent.to_path_buf());
+                    self.tab.pending_hover = Some(child);
+                }
+                None => self.notify(Severity::Info, "Already at the root".to_string()),
+            },
+            Action::Back => command = self.jump(Action::Back, 1),
+            Action::Forward => command = self.jump(Action::Forward, 1),
+            Action::Undo | Action::Redo => {
+                let direction = match action {
+                    Action::Undo => Direction::Undo,
+                    _ => Direction::Redo,
+                };
 
-                *self = new;
-                command = c;
+                if let Some(batch) = self.journal.take(direction) {
+                    self.start_job(JobKind::Revert(direction, batch));
+                }
             }
             Action::AddToSearch(c) => {
-                self.search_term.push(c);
+                self.tab.search_term.push(c);
                 self.refresh_filter()
             }
             Action::PopFromSearch => {
-                let x = self.search_term.pop();
-                info!("search_term: {:?}, {x:?}", self.search_term);
+                let x = self.tab.search_term.pop();
+                info!("search_term: {:?}, {x:?}", self.tab.search_term);
                 self.refresh_filter()
             }
             Action::FreezeSearch => {
-                self.mode = Mode::Normal;
-                self.search_term.clear();
+                self.tab.mode = Mode::Normal;
+                self.tab.search_term.clear();
+            }
+            Action::Rename => {
+                let file = self
+                    .files()
+                    .nth(self.tab.hovered)
+                    .map(|f| (f.data.display_name().into_owned(), f.data.metadata.is_dir()));
+
+                if let Some((name, is_dir)) = file {
+                    // Start out before the extension, that is rarely what's being changed
+                    let cursor = if is_dir {
+                        name.len()
+                    } else {
+                        Path::new(&name)
+                            .file_stem()
+                            .map_or(name.len(), |stem| stem.len())
+                    };
+
+                    self.input = LineInput::new(name, cursor);
+                    self.tab.mode = Mode::Rename;
+                }
+            }
+            Action::CommitCreate(kind) => {
+                self.tab.mode = Mode::Normal;
+                let name = std::mem::take(&mut self.input).text;
+                command = self.take_action(Action::Create(kind, name));
+            }
+            Action::Create(kind, name) => {
+                if !name.is_empty() {
+                    command = Command::perform(
+                        create_entry(self.tab.current_dir.clone(), name, kind),
+                        Message::Created,
+                    );
+                }
+            }
+            Action::BulkRename => {
+                self.end_visual();
+                let paths = self.targets();
+                if !paths.is_empty() {
+                    self.files_mut().for_each(|f| f.selected = false);
+                    command = Command::perform(
+                        bulk_rename(self.tab.current_dir.clone(), paths),
+                        |(ops, result)| Message::BulkRenamed(ops, result),
+                    );
+                }
+            }
+            Action::Edit(edit) => match &mut self.curr_view {
+                View::Bookmarks(b) => match &mut b.renaming {
+                    Some((_, input)) => input.apply(edit),
+                    None => {
+                        b.query.apply(edit);
+                        b.hovered = 0;
+                    }
+                },
+                _ => self.input.apply(edit),
+            },
+            Action::CommitRename => {
+                self.tab.mode = Mode::Normal;
+                let name = std::mem::take(&mut self.input).text;
+                command = self.take_action(Action::RenameTo(name));
+            }
+            Action::RenameTo(name) => {
+                // The prompt starts out with the name as shown, which for a name that isn't
+                // valid UTF-8 isn't quite the name on disk. Leaving it as is changes nothing.
+                let paths = self
+                    .files()
+                    .nth(self.tab.hovered)
+                    .filter(|f| f.data.display_name() != name)
+                    .map(|f| (f.data.path.clone(), f.data.parent.join(&name)));
+
+                if let Some((from, to)) = paths {
+                    if name.is_empty() || name.contains(std::path::MAIN_SEPARATOR) {
+                        self.notify(Severity::Warning, format!("Invalid name {name:?}"));
+                    } else if to != from {
+                        command = Command::perform(rename_file(from, to), Message::Renamed);
+                    }
+                }
+            }
+            Action::RunCommand => {
+                self.tab.mode = Mode::Normal;
+                let line = std::mem::take(&mut self.input).text;
+
+                let save = self
+                    .history
+                    .push(line.clone())
+                    .map(|save| Command::perform(save, Message::Saved));
+
+                let run = match command::parse(&line) {
+                    Ok(action) => self.take_action(action),
+                    Err(e) => {
+                        self.notify(Severity::Warning, format!(":{line}: {e}"));
+                        Command::none()
+                    }
+                };
+
+                command = Command::batch(save.into_iter().chain([run]));
+            }
+            // Completion and history are for `:` commands, not for names being typed in the
+            // other prompts that share their keys
+            Action::Complete if self.tab.mode == Mode::Command => {
+                command = Command::perform(
+                    command::complete(self.input.text.clone(), self.tab.current_dir.clone()),
+                    {
+                        let line = self.input.text.clone();
+                        move |completed| Message::Completed(line, completed)
+                    },
+                );
+            }
+            Action::HistoryOlder if self.tab.mode == Mode::Command => {
+                if let Some(line) = self.history.older() {
+                    self.input = LineInput::new(line.to_string(), line.len());
+                }
+            }
+            Action::HistoryNewer if self.tab.mode == Mode::Command => {
+                let line = self.history.newer();
+                self.input = LineInput::new(line.to_string(), line.len());
+            }
+            Action::Complete | Action::HistoryOlder | Action::HistoryNewer => (),
+            Action::ChangeDir(path) => {
+                let dir = command::normalize(&self.tab.current_dir.join(path));
+                if dir.is_dir() {
+                    command = self.change_dir(dir);
+                } else {
+                    self.notify(
+                        Severity::Warning,
+                        format!("{} is not a directory", dir.display()),
+                    );
+                }
+            }
+            Action::Sort(key) => command = self.change_sort(|sort| sort.key = key),
+            Action::ToggleDirsFirst => {
+                command = self.change_sort(|sort| sort.dirs_first = !sort.dirs_first)
+            }
+            Action::ToggleReverse => {
+                command = self.change_sort(|sort| sort.reverse = !sort.reverse)
+            }
+            Action::RememberSort(remember) => {
+                self.sorts.remember(&self.tab.current_dir, remember);
+                command = Command::perform(self.sorts.save(), Message::Saved);
+            }
+            Action::ShowHidden(show) => command = self.change_filter(|f| f.show_hidden = show),
+            Action::ToggleHidden => {
+                command = self.change_filter(|f| f.show_hidden = !f.show_hidden)
+            }
+            Action::RespectIgnore(respect) => {
+                command = self.change_filter(|f| f.respect_ignore = respect)
+            }
+            Action::ToggleIgnore => {
+                command = self.change_filter(|f| f.respect_ignore = !f.respect_ignore)
+            }
+            Action::RestoreTrashed => {
+                if let View::Trash(t) = &self.curr_view {
+                    if let Some(entry) = t.entries.get(t.hovered) {
+                        let entry = entry.clone();
+                        command = Command::perform(
+                            async move { trash::restore(entry, &mut JobContext::default()).await },
+                            |r| {
+                                Message::TrashResult(
+                                    r.map(|_| ()).map_err(|e| TrashError(e.to_string())),
+                                )
+                            },
+                        )
+                    }
+                }
+            }
+            Action::EmptyTrash => self.start_job(JobKind::EmptyTrash),
+            Action::SetMark(letter) => {
+                self.tab.mode = Mode::Normal;
+
+                if letter.is_ascii_alphabetic() {
+                    self.marks.set(letter, self.tab.current_dir.clone());
+                    self.notify(
+                        Severity::Info,
+                        format!("Marked {} as {letter}", self.tab.current_dir.display()),
+                    );
+                    command = Command::perform(self.marks.save(), Message::Saved);
+                } else {
+                    self.notify(
+                        Severity::Warning,
+                        format!("Marks are letters, not {letter:?}"),
+                    );
+                }
+            }
+            Action::GoToMark(letter) => {
+                self.tab.mode = Mode::Normal;
+
+                command = match (letter, self.marks.get(letter)) {
+                    // Like vim, '' goes back to where the last jump came from
+                    ('\'', _) => self.jump(Action::Back, 1),
+                    (_, Some(mark)) => self.take_action(Action::ChangeDir(mark.dir.clone())),
+                    (_, None) => {
+                        self.notify(Severity::Warning, format!("No mark {letter:?}"));
+                        Command::none()
+                    }
+                };
+            }
+            Action::JumpTo(query) => {
+                // Directories that are gone since they were visited are forgotten on the way,
+                // and like zoxide, the current one is never the answer
+                let mut dirs = self.frecency.matching(&query).into_iter();
+                let mut gone = vec![];
+                let dir = dirs.find_map(|v| {
+                    if v.dir == self.tab.current_dir {
+                        None
+                    } else if v.dir.is_dir() {
+                        Some(v.dir.clone())
+                    } else {
+                        gone.push(v.dir.clone());
+                        None
+                    }
+                });
+
+                for dir in &gone {
+                    self.frecency.remove(dir);
+                }
+
+                match dir {
+                    Some(dir) => command = self.change_dir(dir),
+                    None => self.notify(
+                        Severity::Info,
+                        format!("No visited directory matches {query}"),
+                    ),
+                }
+            }
+            Action::Import(source, path) => {
+                command = Command::perform(frecency::import(source, path), Message::Imported)
+            }
+            Action::PickBookmark => {
+                if let View::Bookmarks(b) = &mut self.curr_view {
+                    if let Some((letter, input)) = b.renaming.take() {
+                        self.marks.rename(letter, input.text);
+                        command = Command::perform(self.marks.save(), Message::Saved);
+                    } else if let Some(dir) = self
+                        .marks
+                        .matching(&b.query.text)
+                        .get(b.hovered)
+                        .map(|m| m.dir.clone())
+                    {
+                        self.curr_view = View::MainView;
+                        command = self.take_action(Action::ChangeDir(dir));
+                    }
+                }
+            }
+            Action::RenameBookmark => {
+                if let View::Bookmarks(b) = &mut self.curr_view {
+                    if let Some(mark) = self.marks.matching(&b.query.text).get(b.hovered) {
+                        let input = LineInput::new(mark.name.clone(), mark.name.len());
+                        b.renaming = Some((mark.letter, input));
+                    }
+                }
+            }
+            Action::CancelBookmarkRename => {
+                if let View::Bookmarks(b) = &mut self.curr_view {
+                    b.renaming = None;
+                }
+            }
+            Action::DeleteBookmark => {
+                if let View::Bookmarks(b) = &mut self.curr_view {
+                    let marks = self.marks.matching(&b.query.text);
+                    let count = marks.len();
+                    if let Some(letter) = marks.get(b.hovered).map(|m| m.letter) {
+                        self.marks.remove(letter);
+                        b.hovered = b.hovered.min(count.saturating_sub(2));
+                        command = Command::perform(self.marks.save(), Message::Saved);
+                    }
+                }
+            }
+            Action::DismissNotifications => self.notifications.dismiss_all(),
+            Action::LongListing(long) => self.long_listing = long,
+            Action::ToggleLongListing => self.long_listing = !self.long_listing,
+            Action::ToggleDetail(detail) => {
+                details::toggle(&mut self.details, detail);
+                self.long_listing = true;
+            }
+            Action::SetDetails(details) => {
+                self.long_listing = !details.is_empty();
+                self.details = details;
+            }
+            Action::TogglePreview => {
+                self.show_preview = !self.show_preview;
+                self.preview = None;
+            }
+            Action::NewTab(path) => {
+                let dir = match path {
+                    Some(path) => command::normalize(&self.tab.current_dir.join(path)),
+                    None => self.tab.current_dir.clone(),
+                };
+
+                if dir.is_dir() {
+                    self.hide_tab();
+                    let tab = std::mem::replace(&mut self.tab, Tab::new(dir));
+                    self.other_tabs.insert(self.active_tab, tab);
+                    self.active_tab += 1;
+                    command = self.show_tab();
+                } else {
+                    self.notify(
+                        Severity::Warning,
+                        format!("{} is not a directory", dir.display()),
+                    );
+                }
+            }
+            Action::CloseTab => {
+                if self.other_tabs.is_empty() {
+                    self.notify(Severity::Info, "Can't close the last tab".to_string());
+                } else {
+                    // The tab to the right takes its place, or the one to the left for the last
+                    let index = self.active_tab.min(self.other_tabs.len() - 1);
+                    self.tab = self.other_tabs.remove(index);
+                    self.active_tab = index;
+                    command = self.show_tab();
+                }
+            }
+            Action::NextTab => command = self.go_to_tab((self.active_tab + 1) % self.tab_count()),
+            Action::PreviousTab => {
+                let count = self.tab_count();
+                command = self.go_to_tab((self.active_tab + count - 1) % count)
+            }
+            Action::GoToTab(index) => command = self.go_to_tab(index),
+            // `tab` isn't in `other_tabs`, so its index alone decides where it sits
+            Action::MoveTabLeft => self.active_tab = self.active_tab.saturating_sub(1),
+            Action::MoveTabRight => {
+                self.active_tab = (self.active_tab + 1).min(self.other_tabs.len())
+            }
+            Action::CopyToTab(index) => command = self.send_to_tab(RegisterKind::Yank, index),
+            Action::MoveToTab(index) => command = self.send_to_tab(RegisterKind::Cut, index),
+            Action::CancelJob => {
+                if let Some(job) = self.jobs.iter().rev().find(|j| !j.is_cancelled()) {
+                    job.cancel();
+                }
             }
             Action::None => (),
-            Action::NewView(view) => self.curr_view = view,
+            Action::NewView(view) => {
+                if let View::Trash(_) = view {
+                    command = load_trash();
+                }
+
+                self.curr_view = view
+            }
         }
 
+        self.select_range();
         command
     }
 
+    /// Selects the files between the visual mode anchor and the hovered file, on top of what
+    /// was selected before.
+    fn select_range(&mut self) {
+        let anchor = match self.tab.mode {
+            Mode::Visual { anchor } => anchor,
+            _ => return,
+        };
+
+        let range = anchor.min(self.tab.hovered)..=anchor.max(self.tab.hovered);
+        let base = &self.tab.visual_base;
+        for (i, f) in self.tab.cache.files_mut().enumerate() {
+            f.selected = range.contains(&i) || base.contains(&f.data.path);
+        }
+    }
+
+    /// Keeps the range selected and goes back to normal mode.
+    fn end_visual(&mut self) {
+        if let Mode::Visual { .. } = self.tab.mode {
+            self.tab.mode = Mode::Normal;
+            self.tab.visual_base.clear();
+        }
+    }
+
+    /// The sort of `current_dir`, and whether it is kept for this directory only.
+    pub fn sort(&self) -> (Sort, bool) {
+        (
+            self.sorts.get(&self.tab.current_dir),
+            self.sorts.is_remembered(&self.tab.current_dir),
+        )
+    }
+
+    /// Changes the sort of `current_dir`, staying on the hovered file.
+    fn change_sort(&mut self, change: impl FnOnce(&mut Sort)) -> Command<Message> {
+        let mut sort = self.sorts.get(&self.tab.current_dir);
+        change(&mut sort);
+        self.sorts.set(&self.tab.current_dir, sort);
+
+        let hovered = self
+            .files()
+            .nth(self.tab.hovered)
+            .map(|f| f.data.path.clone());
+        self.refresh_filter();
+        if let Some(path) = hovered {
+            let idx = self.files().position(|f| f.data.path == path);
+            self.tab.hovered = idx.unwrap_or(self.tab.hovered);
+        }
+
+        Command::perform(self.sorts.save(), Message::Saved)
+    }
+
+    /// Lists `current_dir` again with a different filter, staying on the hovered file if it is
+    /// still listed.
+    fn change_filter(&mut self, change: impl FnOnce(&mut Filter)) -> Command<Message> {
+        change(&mut self.filter);
+        let hovered = self
+            .files()
+            .nth(self.tab.hovered)
+            .map(|f| f.data.path.clone());
+        self.tab.pending_hover = hovered;
+
+        self.reload()
+    }
+
+    /// Selected files, or the hovered one when nothing is selected.
+    fn targets(&self) -> Vec<PathBuf> {
+        if self.files().any(|f| f.selected) {
+            self.files()
+                .filter(|f| f.selected)
+                .map(|f| f.data.path.clone())
+                .collect()
+        } else {
+            self.files()
+                .nth(self.tab.hovered)
+                .map(|f| f.data.path.clone())
+                .into_iter()
+                .collect()
+        }
+    }
+
+    /// Pastes `register` into `dest`, once it is known whether anything there is in the way.
+    fn paste_into(&mut self, register: Register, dest: PathBuf) -> Command<Message> {
+        let count = count_conflicts(register.paths.clone(), dest.clone());
+        self.tab.pending_paste = Some((register, dest.clone()));
+
+        Command::perform(count, move |conflicts| {
+            Message::PasteConflicts(dest, conflicts)
+        })
+    }
+
+    fn paste(&mut self, policy: ConflictPolicy) -> Command<Message> {
+        let (register, dest) = match self.tab.pending_paste.take() {
+            Some(pending) => pending,
+            None => return Command::none(),
+        };
+
+        // Cut files only move once, the register no longer points at them afterwards
+        let moved = |r: &Register| r.paths == register.paths;
+        if register.kind == RegisterKind::Cut && self.register.as_ref().is_some_and(moved) {
+            self.register = None;
+        }
+
+        self.start_job(JobKind::Paste {
+            register,
+            dest,
+            policy,
+        });
+
+        Command::none()
+    }
+
+    /// Deletes `paths`, asking first if any of them are directories.
+    fn delete(&mut self, paths: Vec<PathBuf>) -> Command<Message> {
+        self.tab.pending_delete = paths;
+        self.delete_id += 1;
+
+        let has_dirs = self
+            .files()
+            .any(|f| f.data.metadata.is_dir() && self.tab.pending_delete.contains(&f.data.path));
+
+        if has_dirs {
+            // Asks for confirmation once everything in them is counted
+            let id = self.delete_id;
+            let dir = self.tab.current_dir.clone();
+            Command::perform(
+                count_items(self.tab.pending_delete.clone()),
+                move |(items, bytes)| Message::DeleteCounted(id, dir, items, bytes),
+            )
+        } else {
+            self.delete_pending()
+        }
+    }
+
+    /// Takes an action typed with a count, which scales the actions where that makes sense and
+    /// is ignored otherwise.
+    fn take_counted(&mut self, (action, count): Counted) -> Command<Message> {
+        let count = match count {
+            Some(count) => count,
+            None => return self.take_action(action),
+        };
+
+        match action {
+            Action::Up | Action::Down => {
+                // Moving further than there are files does nothing more
+                let steps = count.min(self.files().count());
+                let commands: Vec<_> = (0..steps)
+                    .map(|_| self.take_action(action.clone()))
+                    .collect();
+
+                Command::batch(commands)
+            }
+            // Like vim's `5G`, go to the nth file
+            Action::Top | Action::Bottom => {
+                let last = self.files().count().saturating_sub(1);
+                self.tab.hovered = count.saturating_sub(1).min(last);
+                self.select_range();
+                Command::none()
+            }
+            Action::Back | Action::Forward => self.jump(action, count),
+            Action::NextTab => self.go_to_tab(count.saturating_sub(1)),
+            Action::PreviousTab => {
+                let tabs = self.tab_count();
+                self.go_to_tab((self.active_tab + tabs - count % tabs) % tabs)
+            }
+            Action::CopyToTab(None) => self.take_action(Action::CopyToTab(Some(count - 1))),
+            Action::MoveToTab(None) => self.take_action(Action::MoveToTab(Some(count - 1))),
+            // A count deletes from the hovered file down, in visual mode the range is deleted
+            Action::Delete if self.tab.mode == Mode::Normal => {
+                let paths = self
+                    .files()
+                    .skip(self.tab.hovered)
+                    .take(count)
+                    .map(|f| f.data.path.clone())
+                    .collect();
+
+                self.delete(paths)
+            }
+            action => self.take_action(action),
+        }
+    }
+
+    /// Trashes `pending_delete` as one batch, so it can be undone as one.
+    fn delete_pending(&mut self) -> Command<Message> {
+        let paths = std::mem::take(&mut self.tab.pending_delete);
+        if paths.is_empty() {
+            return Command::none();
+        }
+
+        let _ = self.tab.cache.drain(|f| paths.contains(&f.data.path));
+        self.tab.hovered = self.tab.hovered.min(self.files().count().saturating_sub(1));
+
+        self.start_job(JobKind::Trash(paths));
+
+        Command::none()
+    }
+
+    /// Every tab, in the order they are shown in.
+    pub fn tabs(&self) -> impl Iterator<Item = &Tab> {
+        let (before, after) = self.other_tabs.split_at(self.active_tab);
+        before.iter().chain(std::iter::once(&self.tab)).chain(after)
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.other_tabs.len() + 1
+    }
+
+    fn go_to_tab(&mut self, index: usize) -> Command<Message> {
+        if index == self.active_tab {
+            return Command::none();
+        }
+        if index >= self.tab_count() {
+            self.notify(Severity::Info, format!("There is no tab {}", index + 1));
+            return Command::none();
+        }
+
+        self.hide_tab();
+        let tab = std::mem::replace(&mut self.tab, Tab::new(PathBuf::new()));
+        self.other_tabs.insert(self.active_tab, tab);
+        self.tab = self.other_tabs.remove(index);
+        self.active_tab = index;
+
+        self.show_tab()
+    }
+
+    /// Drops the delete or paste the shown tab is asking about, before another tab is shown.
+    /// The question would be waiting there unseen otherwise.
+    fn hide_tab(&mut self) {
+        self.tab.pending_delete.clear();
+        self.tab.pending_paste = None;
+        if matches!(
+            self.tab.mode,
+            Mode::ConfirmDelete { .. } | Mode::ResolveConflict { .. }
+        ) {
+            self.tab.mode = Mode::Normal;
+        }
+    }
+
+    /// Lists the directory of a tab that was just switched to again, hidden tabs aren't
+    /// watched.
+    fn show_tab(&mut self) -> Command<Message> {
+        self.preview = None;
+        let hovered = self
+            .files()
+            .nth(self.tab.hovered)
+            .map(|f| f.data.path.clone());
+        self.tab.pending_hover = hovered;
+
+        self.reload()
+    }
+
+    /// Copies or moves the files the next action would act on into the directory of the tab
+    /// at `index`, the next tab by default. They are pasted like after a yank or cut, but the
+    /// register is left alone.
+    fn send_to_tab(&mut self, kind: RegisterKind, index: Option<usize>) -> Command<Message> {
+        let index = index.unwrap_or((self.active_tab + 1) % self.tab_count());
+        if index == self.active_tab {
+            self.notify(
+                Severity::Info,
+                "There is no other tab to send to".to_string(),
+            );
+            return Command::none();
+        }
+
+        let dest = self.tabs().nth(index).map(|tab| tab.current_dir.clone());
+        let dest = match dest {
+            Some(dest) => dest,
+            None => {
+                self.notify(Severity::Info, format!("There is no tab {}", index + 1));
+                return Command::none();
+            }
+        };
+
+        self.end_visual();
+        let paths = self.targets();
+        if paths.is_empty() {
+            return Command::none();
+        }
+        self.files_mut().for_each(|f| f.selected = false);
+
+        self.paste_into(Register { kind, paths }, dest)
+    }
+
+    /// Queues up background work, which starts running as soon as its subscription is picked up.
+    fn start_job(&mut self, kind: JobKind) {
+        self.jobs.push(Job::new(self.next_job_id, kind));
+        self.next_job_id += 1;
+    }
+
+    fn notify(&mut self, severity: Severity, message: String) {
+        self.notifications.push(severity, message);
+    }
+
+    /// Tells the user a file fls keeps its state in couldn't be read, and that it is left alone.
+    fn load_failed(&mut self, error: Option<LoadError>) {
+        if let Some(LoadError { path, reason }) = error {
+            self.notify(
+                Severity::Error,
+                format!(
+                    "Failed to read {}: {reason}. Changes won't be saved to it",
+                    path.display()
+                ),
+            );
+        }
+    }
+
+    /// Moves to `dir`, so the directory that was left can be gone back to.
+    fn change_dir(&mut self, dir: PathBuf) -> Command<Message> {
+        if dir != self.tab.current_dir {
+            let hovered = self
+                .files()
+                .nth(self.tab.hovered)
+                .map(|f| f.data.path.clone());
+            self.tab.jumps.leave(&self.tab.current_dir, hovered);
+        }
+
+        self.frecency.visit(&dir);
+        let save = Command::perform(self.frecency.save(), Message::Saved);
+
+        Command::batch([self.enter_dir(dir), save])
+    }
+
+    /// Goes `steps` back or forward in the jumplist, once directories removed since they were
+    /// visited are found so they can be skipped over.
+    fn jump(&mut self, direction: Action, steps: usize) -> Command<Message> {
+        let dirs = match direction {
+            Action::Back => self.tab.jumps.back_dirs(),
+            _ => self.tab.jumps.forward_dirs(),
+        };
+        let dir = self.tab.current_dir.clone();
+
+        Command::perform(missing_dirs(dirs.to_vec()), move |missing| {
+            Message::JumpChecked(dir.clone(), direction.clone(), steps, missing)
+        })
+    }
+
+    /// Steps through the jumplist once the missing directories have been dropped from it.
+    fn step_jumps(&mut self, direction: Action, steps: usize) -> Command<Message> {
+        let hovered = self
+            .files()
+            .nth(self.tab.hovered)
+            .map(|f| f.data.path.clone());
+        let dir = match direction {
+            Action::Back => self.tab.jumps.back(&self.tab.current_dir, hovered, steps),
+            _ => self
+                .tab
+                .jumps
+                .forward(&self.tab.current_dir, hovered, steps),
+        };
+
+        match dir {
+            Some(dir) => self.enter_dir(dir),
+            None => {
+                let message = match direction {
+                    Action::Back => "Nothing to go back to",
+                    _ => "Nothing to go forward to",
+                };
+                self.notify(Severity::Info, message.to_string());
+                Command::none()
+            }
+        }
+    }
+
+    /// Shows `dir`, resetting everything tied to the directory that was left. The file hovered
+    /// there last time is hovered again.
+    fn enter_dir(&mut self, dir: PathBuf) -> Command<Message> {
+        self.tab.pending_hover = self.tab.jumps.hovered(&dir).cloned();
+        self.tab.pending_delete.clear();
+        self.tab.current_dir = dir;
+        self.tab.cache = Files::new();
+        self.tab.mode = Mode::Normal;
+        self.tab.search_term.clear();
+        self.tab.hovered = 0;
+        self.tab.loaded_depth = 0;
+
+        self.reload()
+    }
+
+    /// Lists `current_dir` again, as deep as it is currently loaded.
+    fn reload(&self) -> Command<Message> {
+        list(
+            self.tab.current_dir.clone(),
+            self.tab.loaded_depth,
+            self.filter,
+        )
+    }
+
     fn refresh_filter(&mut self) {
+        self.rescore();
+        self.tab.hovered = 0;
+    }
+
+    /// Scores and sorts the files again, for a new search term or changed files.
+    fn rescore(&mut self) {
         let matcher = SkimMatcherV2::default();
 
-        // TODO sort
-        if !self.search_term.is_empty() {
-            self.cache.new_scores(|f| {
+        let max_depth = match self.tab.mode {
+            Mode::Search(SearchMode::Global(depth)) => depth,
+            _ => 0,
+        };
+
+        if !self.tab.search_term.is_empty() {
+            self.tab.cache.new_scores(|f| {
+                if f.depth > max_depth {
+                    return -1;
+                }
+
                 matcher
-                    .fuzzy_match(&f.name, &self.search_term)
+                    .fuzzy_match(
+                        &f.relative_path(&self.tab.current_dir).to_string_lossy(),
+                        &self.tab.search_term,
+                    )
                     .unwrap_or(-1)
             })
         } else {
-            self.cache.new_scores(|_| i64::MAX);
+            self.tab
+                .cache
+                .new_scores(|f| if f.depth > max_depth { -1 } else { i64::MAX });
         }
 
-        self.hovered = 0;
+        let sort = self.sorts.get(&self.tab.current_dir);
+        self.tab.cache.sort(sort, !self.tab.search_term.is_empty());
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct File {
     pub metadata: Metadata,
-    pub name: String,
+    /// Kept as is, names don't have to be UTF-8.
+    pub name: OsString,
     pub path: PathBuf,
     pub parent: PathBuf,
     pub depth: usize,
+    /// Where the file points, for symlinks.
+    pub link_target: Option<PathBuf>,
 }
 
 impl File {
     pub fn new(
-        name: String,
+        name: OsString,
         depth: usize,
         path: PathBuf,
         parent: PathBuf,
@@ -352,8 +1786,34 @@ impl File {
             path,
             parent,
             metadata,
+            link_target: None,
         }
     }
+
+    /// The name with anything that isn't UTF-8 replaced, for showing and matching.
+    pub fn display_name(&self) -> Cow<'_, str> {
+        self.name.to_string_lossy()
+    }
+
+    /// Path of the file relative to `root`, used to tell apart results from a global search.
+    pub fn relative_path(&self, root: &Path) -> &Path {
+        self.path.strip_prefix(root).unwrap_or(&self.path)
+    }
+}
+
+/// Lists `dir` in the background, along with `depth` levels of subdirectories.
+fn list(dir: PathBuf, depth: usize, filter: Filter) -> Command<Message> {
+    Command::perform(
+        get_files(dir.clone(), depth, filter),
+        move |(files, errors)| Message::FilesLoaded(dir.clone(), files, errors),
+    )
+}
+
+fn load_trash() -> Command<Message> {
+    Command::perform(trash::list(), |r| match r {
+        Ok(entries) => Message::TrashLoaded(entries),
+        Err(e) => Message::TrashResult(Err(TrashError(e.to_string()))),
+    })
 }
 
 impl PartialEq for File {
@@ -376,15 +1836,108 @@ impl From<File> for DisplayedFile {
 pub enum Action {
     Up,
     Down,
+    /// First file, or the nth one with a count.
+    Top,
+    Bottom,
     UpDir,
     Open,
+    /// Back to the directory visited before, like vim's `C-o`.
+    Back,
+    Forward,
 
     Delete,
+    ConfirmDelete,
+    CancelDelete,
     ToggleCurrent,
+    /// Starts selecting a range from the hovered file.
+    VisualMode,
+    InvertSelection,
+    /// Selects every file shown, i.e. everything matching the search.
+    SelectAll,
+
+    Yank,
+    Cut,
+    Paste,
+    PasteWith(ConflictPolicy),
+
+    RestoreTrashed,
+    EmptyTrash,
+
+    /// Saves the current directory under a letter.
+    SetMark(char),
+    GoToMark(char),
+    /// Jumps to the most frecent visited directory matching the text.
+    JumpTo(String),
+    /// Adds the directories in another tool's database, from its default place without a path.
+    Import(ImportSource, Option<PathBuf>),
+    /// Jumps to the hovered bookmark, or finishes renaming it.
+    PickBookmark,
+    RenameBookmark,
+    CancelBookmarkRename,
+    DeleteBookmark,
+
+    /// Starts renaming the hovered file.
+    Rename,
+    Edit(Edit),
+    CommitRename,
+    RenameTo(String),
+    CommitCreate(CreateKind),
+    Create(CreateKind, String),
+    RunCommand,
+    /// Tab completion in the command prompt.
+    Complete,
+    HistoryOlder,
+    HistoryNewer,
+    ChangeDir(PathBuf),
+    /// Renames the selected files through `$EDITOR`.
+    BulkRename,
+
+    Sort(SortKey),
+    ToggleDirsFirst,
+    ToggleReverse,
+    /// Whether the current directory keeps its own sort.
+    RememberSort(bool),
+    ShowHidden(bool),
+    ToggleHidden,
+    /// Whether `.gitignore` and `.ignore` files are respected.
+    RespectIgnore(bool),
+    ToggleIgnore,
+
+    Undo,
+    Redo,
+    /// Cancels the most recently started job.
+    CancelJob,
+    DismissNotifications,
+
+    /// Opens a tab next to the current one, in the current directory unless given another.
+    NewTab(Option<PathBuf>),
+    CloseTab,
+    /// The next tab, or the nth one with a count like vim's `gt`.
+    NextTab,
+    PreviousTab,
+    GoToTab(usize),
+    MoveTabLeft,
+    MoveTabRight,
+    /// Copies the selected or hovered files into the directory of the tab at this index, the
+    /// next one by default.
+    CopyToTab(Option<usize>),
+    MoveToTab(Option<usize>),
+    TogglePreview,
+    LongListing(bool),
+    ToggleLongListing,
+    /// Shows or hides a column of the long listing, turning it on.
+    ToggleDetail(Detail),
+    /// Picks the columns of the long listing, none turns it off.
+    SetDetails(Vec<Detail>),
 
     NewMode(Mode),
     NewView(View),
     AddToSearch(char),
+    /// Walks one more, or one less, level of subdirectories in a global search.
+    DeeperSearch,
+    ShallowerSearch,
+    /// Finishes whatever prompt is open.
+    Commit,
     PopFromSearch,
     FreezeSearch,
 
@@ -393,9 +1946,12 @@ pub enum Action {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum View {
     MainView,
     Settings(SettingsView),
+    Trash(TrashView),
+    Bookmarks(BookmarksView),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -410,6 +1966,21 @@ pub struct SettingsView {
     pub secondary_input: String,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct TrashView {
+    pub entries: Vec<TrashEntry>,
+    pub hovered: usize,
+}
+
+/// Marks, narrowed down by typing part of their name or directory.
+#[derive(Debug, Clone, Default)]
+pub struct BookmarksView {
+    pub query: LineInput,
+    pub hovered: usize,
+    /// Letter of the mark being renamed, along with its new name.
+    pub renaming: Option<(char, LineInput)>,
+}
+
 impl View {
     fn parse_settings(event: Event) -> Action {
         if let Event::KeyPressed {
@@ -427,4 +1998,36 @@ impl View {
             Action::None
         }
     }
+
+    fn parse_bookmarks(view: &BookmarksView, event: Event) -> Action {
+        match event {
+            Event::KeyPressed {
+                key_code,
+                modifiers,
+            } => {
+                let ctrl = modifiers.contains(Modifiers::CTRL);
+                match key_code {
+                    KeyCode::Down => Action::Down,
+                    KeyCode::N if ctrl => Action::Down,
+                    KeyCode::Up => Action::Up,
+                    KeyCode::P if ctrl => Action::Up,
+                    KeyCode::Enter => Action::PickBookmark,
+                    KeyCode::R if ctrl => Action::RenameBookmark,
+                    KeyCode::D if ctrl => Action::DeleteBookmark,
+                    KeyCode::Backspace => Action::Edit(Edit::Backspace),
+                    KeyCode::Delete => Action::Edit(Edit::Delete),
+                    KeyCode::Left => Action::Edit(Edit::Left),
+                    KeyCode::Right => Action::Edit(Edit::Right),
+                    KeyCode::Home => Action::Edit(Edit::Home),
+                    KeyCode::End => Action::Edit(Edit::End),
+                    KeyCode::Escape if view.renaming.is_some() => Action::CancelBookmarkRename,
+                    KeyCode::Escape => Action::NewView(View::MainView),
+                    _ => Action::None,
+                }
+            }
+            // Typing narrows down the list, or goes into the new name
+            Event::CharacterReceived(c) if !c.is_control() => Action::Edit(Edit::Insert(c)),
+            _ => Action::None,
+        }
+    }
 }
@@ -0,0 +1,319 @@
+//! Renaming many files at once by editing their names in `$EDITOR`, like vidir or ranger's
+//! bulkrename.
+
+use std::{
+    collections::HashSet,
+    env,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process::{self, ExitStatus},
+};
+
+use tokio::{
+    fs::{read_to_string, rename, try_exists},
+    process::Command,
+};
+
+use crate::{app::BulkRenameError, journal::Operation, tasks::free_name};
+
+/// Lets the user edit the names of `paths`, relative to `dir`, and applies whatever changed.
+///
+/// Every file is first moved to a temporary name and only then to its new one, so swaps and
+/// longer cycles can't clobber each other. Both steps are journaled, which keeps undo just as
+/// safe. Operations done before a failure are returned along with the error, and files left
+/// under their temporary name by it are moved back.
+pub async fn bulk_rename(
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+) -> (Vec<Operation>, Result<(), BulkRenameError>) {
+    let renames = match edit_names(&dir, &paths).await {
+        Ok(renames) => renames,
+        Err(e) => return (vec![], Err(e)),
+    };
+
+    let mut ops = vec![];
+
+    let mut staged = vec![];
+    for (i, (from, to)) in renames.into_iter().enumerate() {
+        // `rename` replaces whatever is there, a leftover from an earlier run included
+        let tmp = from.with_file_name(format!(".fls-rename-{}-{i}", process::id()));
+        let staged_op = match try_exists(&tmp).await {
+            Ok(false) => rename_op(from.clone(), tmp.clone(), &mut ops).await,
+            Ok(true) => Err(BulkRenameError(format!("{} is in the way", tmp.display()))),
+            Err(e) => Err(BulkRenameError(format!("{}: {e}", tmp.display()))),
+        };
+        if let Err(e) = staged_op {
+            let staged = staged.into_iter().map(|(tmp, from, _)| (tmp, from));
+            let e = unstage(staged.collect(), e, &mut ops).await;
+            return (ops, Err(e));
+        }
+
+        staged.push((tmp, from, to));
+    }
+
+    let mut staged = staged.into_iter();
+    while let Some((tmp, from, to)) = staged.next() {
+        if let Err(e) = rename_op(tmp.clone(), to, &mut ops).await {
+            let remaining = staged.map(|(tmp, from, _)| (tmp, from));
+            let remaining = std::iter::once((tmp, from)).chain(remaining);
+            let e = unstage(remaining.collect(), e, &mut ops).await;
+            return (ops, Err(e));
+        }
+    }
+
+    (ops, Ok(()))
+}
+
+/// Moves files that are still under their hidden temporary name back to where they came from,
+/// after `error` stopped the renames. A file that took the original name in the meantime keeps
+/// it, the one moved back gets a free name next to it.
+async fn unstage(
+    staged: Vec<(PathBuf, PathBuf)>,
+    error: BulkRenameError,
+    ops: &mut Vec<Operation>,
+) -> BulkRenameError {
+    let mut message = error.0;
+
+    for (tmp, from) in staged {
+        let back = match try_exists(&from).await {
+            Ok(false) => from,
+            _ => free_name(&from).await,
+        };
+        if let Err(e) = rename_op(tmp, back, ops).await {
+            message.push_str(&format!(", and couldn't move back {}", e.0));
+        }
+    }
+
+    BulkRenameError(message)
+}
+
+async fn rename_op(
+    from: PathBuf,
+    to: PathBuf,
+    ops: &mut Vec<Operation>,
+) -> Result<(), BulkRenameError> {
+    rename(&from, &to)
+        .await
+        .map_err(|e| BulkRenameError(format!("{} -> {}: {e}", from.display(), to.display())))?;
+
+    ops.push(Operation::Renamed { from, to });
+
+    Ok(())
+}
+
+/// Opens the names in the editor and returns the renames to make, or why they can't be made.
+async fn edit_names(
+    dir: &Path,
+    paths: &[PathBuf],
+) -> Result<Vec<(PathBuf, PathBuf)>, BulkRenameError> {
+    let names: Vec<_> = paths
+        .iter()
+        .map(|p| {
+            p.strip_prefix(dir)
+                .unwrap_or(p)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+
+    let mut contents = names.join("\n");
+    contents.push('\n');
+
+    // A fresh file with a random name, only readable by us, so nobody else can plant a
+    // symlink there or swap in names of their own
+    let edited = async {
+        let mut file = tempfile::Builder::new()
+            .prefix("fls-bulkrename-")
+            .suffix(".txt")
+            .tempfile()?;
+        file.write_all(contents.as_bytes())?;
+        file.flush()?;
+
+        let status = run_editor(file.path()).await?;
+        read_to_string(file.path())
+            .await
+            .map(|edited| (status, edited))
+    }
+    .await;
+
+    let edited = match edited {
+        Ok((status, edited)) if status.success() => edited,
+        Ok((status, _)) => return Err(BulkRenameError(format!("editor exited with {status}"))),
+        Err(e) => return Err(BulkRenameError(format!("editor: {e}"))),
+    };
+
+    let renames = plan_renames(dir, paths, &names, &edited)?;
+
+    // Targets may only exist if they are being renamed away as well
+    for (_, to) in &renames {
+        let freed = renames.iter().any(|(from, _)| from == to);
+        if !freed && try_exists(to).await.unwrap_or(true) {
+            return Err(BulkRenameError(format!("{} already exists", to.display())));
+        }
+    }
+
+    Ok(renames)
+}
+
+/// Matches the edited lines up with `paths` and their original `names`, returning the renames
+/// for the lines that changed. Only the last part of a name may change, files don't move to
+/// other directories.
+fn plan_renames(
+    dir: &Path,
+    paths: &[PathBuf],
+    names: &[String],
+    edited: &str,
+) -> Result<Vec<(PathBuf, PathBuf)>, BulkRenameError> {
+    // Names may start or end with spaces, only a line ending from the editor is left out
+    let new_names: Vec<_> = edited
+        .split_terminator('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+
+    if new_names.len() != names.len() {
+        return Err(BulkRenameError(format!(
+            "expected {} lines but got {}, lines map to files by position so none may be added \
+             or removed",
+            names.len(),
+            new_names.len()
+        )));
+    }
+
+    let mut renames = vec![];
+    let mut targets = HashSet::new();
+    for (line, ((path, old), new)) in paths.iter().zip(names).zip(new_names).enumerate() {
+        if new == old {
+            continue;
+        }
+
+        if new.is_empty() {
+            return Err(BulkRenameError(format!("{old} was given an empty name")));
+        }
+
+        let new_path = Path::new(new);
+        let plain = new_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if !plain || new_path.parent() != Path::new(old).parent() {
+            return Err(BulkRenameError(format!(
+                "line {}: {new} would move {old} to another directory, only names can change",
+                line + 1
+            )));
+        }
+
+        let to = dir.join(new);
+        if !targets.insert(to.clone()) {
+            return Err(BulkRenameError(format!("{new} is used more than once")));
+        }
+
+        renames.push((path.clone(), to));
+    }
+
+    Ok(renames)
+}
+
+/// Runs `$VISUAL` or `$EDITOR` on `file` and waits for it to exit.
+async fn run_editor(file: &Path) -> io::Result<ExitStatus> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Allow things like `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "$EDITOR is empty"))?;
+
+    Command::new(program).args(words).arg(file).status().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(names: &[&str], edited: &str) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+        let dir = Path::new("/dir");
+        let paths: Vec<_> = names.iter().map(|n| dir.join(n)).collect();
+        let names: Vec<_> = names.iter().map(|n| n.to_string()).collect();
+
+        plan_renames(dir, &paths, &names, edited).map_err(|e| e.0)
+    }
+
+    #[test]
+    fn unchanged_lines_are_left_alone() {
+        assert_eq!(plan(&["a", "b"], "a\nb\n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn changed_lines_are_renamed() {
+        assert_eq!(
+            plan(&["a", "b"], "a\nc\n"),
+            Ok(vec![(PathBuf::from("/dir/b"), PathBuf::from("/dir/c"))])
+        );
+    }
+
+    #[test]
+    fn surrounding_spaces_are_part_of_the_name() {
+        assert_eq!(plan(&[" notes", "a "], " notes\na \n"), Ok(vec![]));
+        assert_eq!(
+            plan(&["a"], " a\n"),
+            Ok(vec![(PathBuf::from("/dir/a"), PathBuf::from("/dir/ a"))])
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_are_stripped() {
+        assert_eq!(plan(&["a", "b"], "a\r\nb\r\n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn missing_final_newline_is_fine() {
+        assert_eq!(plan(&["a", "b"], "a\nb"), Ok(vec![]));
+    }
+
+    #[test]
+    fn swaps_are_allowed() {
+        assert_eq!(
+            plan(&["a", "b"], "b\na\n"),
+            Ok(vec![
+                (PathBuf::from("/dir/a"), PathBuf::from("/dir/b")),
+                (PathBuf::from("/dir/b"), PathBuf::from("/dir/a")),
+            ])
+        );
+    }
+
+    #[test]
+    fn added_or_removed_lines_are_rejected() {
+        assert!(plan(&["a", "b"], "a\n").is_err());
+        assert!(plan(&["a"], "a\nb\n").is_err());
+    }
+
+    #[test]
+    fn files_stay_in_their_directory() {
+        for edited in ["/etc/x\n", "../x\n", "sub/x\n", "./x\n", "x/..\n"] {
+            assert!(plan(&["a"], edited).is_err(), "{edited:?}");
+        }
+
+        let error = plan(&["a", "b"], "a\n../b\n").unwrap_err();
+        assert!(error.starts_with("line 2:"), "{error}");
+    }
+
+    #[test]
+    fn files_in_subdirectories_keep_their_directory() {
+        assert_eq!(
+            plan(&["sub/a"], "sub/b\n"),
+            Ok(vec![(
+                PathBuf::from("/dir/sub/a"),
+                PathBuf::from("/dir/sub/b")
+            )])
+        );
+        assert!(plan(&["sub/a"], "b\n").is_err());
+        assert!(plan(&["sub/a"], "other/a\n").is_err());
+    }
+
+    #[test]
+    fn empty_and_duplicate_names_are_rejected() {
+        assert!(plan(&["a", "b"], "a\n\n").is_err());
+        assert!(plan(&["a", "b"], "c\nc\n").is_err());
+    }
+}
@@ -0,0 +1,496 @@
+//! `:` commands typed into the bottom bar, their completion and their history.
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use tokio::fs::{read_dir, read_to_string};
+
+use crate::{
+    app::{Action, LoadError, SaveError},
+    details::Detail,
+    dirs,
+    frecency::ImportSource,
+    mode::Mode,
+    sort::SortKey,
+    tasks::CreateKind,
+};
+
+/// Every command name, for completion.
+const COMMANDS: &[&str] = &[
+    "cd",
+    "copyto",
+    "import",
+    "jump",
+    "mkdir",
+    "moveto",
+    "q",
+    "quit",
+    "rename",
+    "sort",
+    "set",
+    "tabclose",
+    "tabnew",
+    "tabnext",
+    "tabprevious",
+    "touch",
+    "z",
+];
+
+/// Commands whose argument is a path, relative to the current directory.
+const PATH_COMMANDS: &[&str] = &["cd", "mkdir", "rename", "tabnew", "touch"];
+
+/// How many commands are kept in the history file.
+const HISTORY_LEN: usize = 1000;
+
+/// Turns a command line, with or without the leading `:`, into the action it stands for.
+pub fn parse(line: &str) -> Result<Action, String> {
+    let line = line.trim().trim_start_matches(':');
+    let (name, arg) = split_command(line);
+    let arg = arg.unwrap_or_default();
+
+    let action = match (name, arg) {
+        ("", _) => Action::None,
+        ("q" | "quit", "") => Action::Quit,
+        ("cd", "") => Action::ChangeDir(dirs::home()),
+        ("cd", path) => Action::ChangeDir(expand_tilde(path)),
+        ("tabnew", "") => Action::NewTab(None),
+        ("tabnew", path) => Action::NewTab(Some(expand_tilde(path))),
+        ("tabclose", "") => Action::CloseTab,
+        ("tabnext", "") => Action::NextTab,
+        ("tabnext", _) | ("copyto", _) | ("moveto", _) => {
+            // Tabs are numbered from 1 like in the tab strip
+            let index = match arg.parse::<usize>() {
+                Ok(number) if number > 0 => number - 1,
+                _ => return Err(format!("{name} takes the number of a tab")),
+            };
+
+            match name {
+                "tabnext" => Action::GoToTab(index),
+                "copyto" => Action::CopyToTab(Some(index)),
+                _ => Action::MoveToTab(Some(index)),
+            }
+        }
+        ("tabprevious", "") => Action::PreviousTab,
+        ("jump" | "z", "") => Action::NewMode(Mode::Jump),
+        ("jump" | "z", query) => Action::JumpTo(query.to_string()),
+        ("import", arg) => {
+            let (source, path) = match arg.split_once(char::is_whitespace) {
+                Some((source, path)) => (source, Some(expand_tilde(path.trim()))),
+                None => (arg, None),
+            };
+
+            match source {
+                "zoxide" => Action::Import(ImportSource::Zoxide, path),
+                "autojump" => Action::Import(ImportSource::Autojump, path),
+                _ => return Err("import takes zoxide or autojump, and a path to read".to_string()),
+            }
+        }
+        ("mkdir", "") | ("touch", "") => return Err(format!("{name} needs a name")),
+        ("mkdir", path) => Action::Create(CreateKind::Directory, path.to_string()),
+        ("touch", path) => Action::Create(CreateKind::File, path.to_string()),
+        ("rename", "") => Action::Rename,
+        ("rename", new_name) => Action::RenameTo(new_name.to_string()),
+        ("sort", "reverse") => Action::ToggleReverse,
+        ("sort", "dirs_first") => Action::ToggleDirsFirst,
+        ("sort", "remember") => Action::RememberSort(true),
+        ("sort", "forget") => Action::RememberSort(false),
+        ("sort", key) => match SortKey::from_name(key) {
+            Some(key) => Action::Sort(key),
+            None => {
+                return Err(
+                    "sort takes name, size, modified, extension, type, reverse, dirs_first, \
+                     remember or forget"
+                        .to_string(),
+                )
+            }
+        },
+        ("set", "hidden") => Action::ShowHidden(true),
+        ("set", "nohidden") => Action::ShowHidden(false),
+        ("set", "hidden!") => Action::ToggleHidden,
+        ("set", "ignore") => Action::RespectIgnore(true),
+        ("set", "noignore") => Action::RespectIgnore(false),
+        ("set", "ignore!") => Action::ToggleIgnore,
+        ("set", "long") => Action::LongListing(true),
+        ("set", "nolong") => Action::LongListing(false),
+        ("set", "long!") => Action::ToggleLongListing,
+        ("set", option) if option.starts_with("columns=") => {
+            let names = option.trim_start_matches("columns=");
+            let details: Option<Vec<_>> = names
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(Detail::from_name)
+                .collect();
+
+            match details {
+                Some(details) => Action::SetDetails(details),
+                None => {
+                    return Err(
+                        "columns are size, modified, permissions, owner and target".to_string()
+                    )
+                }
+            }
+        }
+        ("set", _) => {
+            return Err(
+                "set takes hidden, ignore, long or columns=a,b,.., no turns off, ! toggles"
+                    .to_string(),
+            )
+        }
+        (name, _) if COMMANDS.contains(&name) => {
+            return Err(format!("{name} doesn't take arguments"))
+        }
+        (name, _) => return Err(format!("unknown command {name}")),
+    };
+
+    Ok(action)
+}
+
+/// Splits a line into the command name and its argument, if there is whitespace after the
+/// name.
+fn split_command(line: &str) -> (&str, Option<&str>) {
+    match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, Some(arg.trim())),
+        None => (line, None),
+    }
+}
+
+/// Completes the command name, or the path being typed as its argument, as far as it is
+/// unambiguous.
+pub async fn complete(line: String, dir: PathBuf) -> String {
+    let (name, arg) = match split_command(&line) {
+        (name, Some(arg)) => (name, arg),
+        (_, None) => {
+            let matches: Vec<_> = COMMANDS
+                .iter()
+                .filter(|c| c.starts_with(line.as_str()))
+                .copied()
+                .collect();
+
+            return match matches[..] {
+                [only] => format!("{only} "),
+                _ => common_prefix(&matches).unwrap_or(line),
+            };
+        }
+    };
+
+    if !PATH_COMMANDS.contains(&name) {
+        return line;
+    }
+
+    // Only what comes after the last `/` is completed, the rest picks the directory to look in
+    let (base, prefix) = match arg.rfind('/') {
+        Some(i) => arg.split_at(i + 1),
+        None => ("", arg),
+    };
+    let search_dir = normalize(&dir.join(expand_tilde(base)));
+
+    let mut matches = vec![];
+    if let Ok(mut entries) = read_dir(&search_dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let hidden = file_name.starts_with('.') && !prefix.starts_with('.');
+            if hidden || !file_name.starts_with(prefix) {
+                continue;
+            }
+
+            let is_dir = matches!(entry.file_type().await, Ok(t) if t.is_dir());
+            if name == "cd" && !is_dir {
+                continue;
+            }
+
+            matches.push((file_name, is_dir));
+        }
+    }
+
+    let completed = match &matches[..] {
+        [(only, true)] => format!("{only}/"),
+        [(only, false)] => only.clone(),
+        _ => {
+            let names: Vec<_> = matches.iter().map(|(name, _)| name.as_str()).collect();
+            common_prefix(&names).unwrap_or_else(|| prefix.to_string())
+        }
+    };
+
+    format!("{name} {base}{completed}")
+}
+
+fn common_prefix(words: &[&str]) -> Option<String> {
+    let (first, rest) = words.split_first()?;
+    let len = rest.iter().fold(first.len(), |len, word| {
+        first
+            .char_indices()
+            .zip(word.chars())
+            .take_while(|((i, a), b)| *i < len && a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+    });
+
+    Some(first[..len].to_string())
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            dirs::home().join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Resolves `.` and `..` without touching the filesystem, so symlinks are kept as they were
+/// walked through.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                let _ = normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
+
+/// Previously run commands, oldest first, browsed with up and down in the prompt.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// Entry currently shown in the prompt, `None` when typing a new command.
+    position: Option<usize>,
+    /// The history file couldn't be read, so new commands aren't written over it.
+    unreadable: bool,
+}
+
+impl History {
+    fn path() -> PathBuf {
+        dirs::data_dir().join("history")
+    }
+
+    /// Reads the commands run before, or why they couldn't be.
+    pub async fn load() -> (History, Option<LoadError>) {
+        let path = Self::path();
+        match read_to_string(&path).await {
+            Ok(contents) => {
+                let history = History {
+                    entries: contents.lines().map(str::to_string).collect(),
+                    ..History::default()
+                };
+                (history, None)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (History::default(), None),
+            Err(e) => {
+                let history = History {
+                    unreadable: true,
+                    ..History::default()
+                };
+                let error = LoadError {
+                    path,
+                    reason: e.to_string(),
+                };
+                (history, Some(error))
+            }
+        }
+    }
+
+    /// Adds the loaded history in front of the commands run so far, history loading late
+    /// shouldn't lose new commands.
+    pub fn extend_front(&mut self, older: History) {
+        let mut entries = older.entries;
+        entries.append(&mut self.entries);
+        self.entries = entries;
+        self.position = None;
+        self.unreadable |= older.unreadable;
+    }
+
+    /// Records a command that was just run, returning the file write that persists it.
+    pub fn push(
+        &mut self,
+        line: String,
+    ) -> Option<impl std::future::Future<Output = Result<(), SaveError>>> {
+        self.position = None;
+
+        if line.trim().is_empty() || self.entries.last() == Some(&line) {
+            return None;
+        }
+
+        self.entries.push(line);
+        if self.entries.len() > HISTORY_LEN {
+            let _ = self.entries.drain(..self.entries.len() - HISTORY_LEN);
+        }
+
+        if self.unreadable {
+            return None;
+        }
+
+        let path = Self::path();
+        let mut contents = self.entries.join("\n");
+        contents.push('\n');
+        let save = dirs::save_text(path.clone(), contents);
+        Some(async move {
+            save.await.map_err(|e| SaveError {
+                path,
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    pub fn older(&mut self) -> Option<&str> {
+        let position = match self.position {
+            Some(0) => 0,
+            Some(p) => p - 1,
+            None => self.entries.len().checked_sub(1)?,
+        };
+
+        self.position = Some(position);
+        self.entries.get(position).map(String::as_str)
+    }
+
+    /// The next newer entry, or an empty line once past the newest.
+    pub fn newer(&mut self) -> &str {
+        match self.position {
+            Some(p) if p + 1 < self.entries.len() => {
+                self.position = Some(p + 1);
+                &self.entries[p + 1]
+            }
+            _ => {
+                self.position = None;
+                ""
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(parse("q"), Ok(Action::Quit)));
+        assert!(matches!(parse(":quit"), Ok(Action::Quit)));
+        assert!(matches!(parse(""), Ok(Action::None)));
+        assert!(matches!(parse("tabnext 2"), Ok(Action::GoToTab(1))));
+        assert!(matches!(parse("copyto 1"), Ok(Action::CopyToTab(Some(0)))));
+        assert!(matches!(
+            parse("sort size"),
+            Ok(Action::Sort(SortKey::Size))
+        ));
+        assert!(matches!(parse("set hidden!"), Ok(Action::ToggleHidden)));
+    }
+
+    #[test]
+    fn arguments_are_split_off_at_any_whitespace() {
+        assert!(matches!(parse("  :cd\tsrc  "), Ok(Action::ChangeDir(p)) if p == Path::new("src")));
+        assert!(matches!(parse("cd  a b"), Ok(Action::ChangeDir(p)) if p == Path::new("a b")));
+        assert!(matches!(
+            parse("import zoxide  /tmp/db.zo"),
+            Ok(Action::Import(ImportSource::Zoxide, Some(p))) if p == Path::new("/tmp/db.zo")
+        ));
+    }
+
+    #[test]
+    fn cd_without_a_path_goes_home() {
+        assert!(matches!(parse("cd"), Ok(Action::ChangeDir(p)) if p == dirs::home()));
+    }
+
+    #[test]
+    fn parses_columns() {
+        let columns = parse("set columns=size,owner");
+        assert!(matches!(
+            columns,
+            Ok(Action::SetDetails(d)) if d == [Detail::Size, Detail::Owner]
+        ));
+        assert!(parse("set columns=size,colour").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert!(parse("nope").is_err());
+        assert!(parse("q now").is_err());
+        assert!(parse("tabnext 0").is_err());
+        assert!(parse("tabnext x").is_err());
+        assert!(parse("mkdir").is_err());
+        assert!(parse("import fasd").is_err());
+    }
+
+    #[test]
+    fn common_prefix_of_words() {
+        assert_eq!(common_prefix(&["abc", "abd"]), Some("ab".to_string()));
+        assert_eq!(common_prefix(&["abc", "abc"]), Some("abc".to_string()));
+        assert_eq!(common_prefix(&["abc", "x"]), Some(String::new()));
+        assert_eq!(common_prefix(&["äb", "äc"]), Some("ä".to_string()));
+        assert_eq!(common_prefix(&["only"]), Some("only".to_string()));
+        assert_eq!(common_prefix(&[]), None);
+    }
+
+    #[test]
+    fn expands_tilde() {
+        assert_eq!(expand_tilde("~"), dirs::home());
+        assert_eq!(expand_tilde("~/docs"), dirs::home().join("docs"));
+        assert_eq!(expand_tilde("~user/docs"), PathBuf::from("~user/docs"));
+        assert_eq!(expand_tilde("docs/~"), PathBuf::from("docs/~"));
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize(Path::new("/a/./b/../c")), PathBuf::from("/a/c"));
+        assert_eq!(normalize(Path::new("/a/b/../..")), PathBuf::from("/"));
+        assert_eq!(normalize(Path::new("/..")), PathBuf::from("/"));
+    }
+
+    fn complete_in(dir: &Path, line: &str) -> String {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime")
+            .block_on(complete(line.to_string(), dir.to_path_buf()))
+    }
+
+    #[test]
+    fn completes_command_names() {
+        let dir = Path::new("/");
+        assert_eq!(complete_in(dir, "tabc"), "tabclose ");
+        assert_eq!(complete_in(dir, "tabn"), "tabne");
+        assert_eq!(complete_in(dir, "xyz"), "xyz");
+    }
+
+    #[test]
+    fn completes_paths() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        fs::create_dir(dir.path().join("docs")).expect("docs");
+        fs::create_dir(dir.path().join("downloads")).expect("downloads");
+        fs::create_dir(dir.path().join(".hidden")).expect("hidden");
+        fs::write(dir.path().join("notes.txt"), "").expect("notes");
+
+        let dir = dir.path();
+        assert_eq!(complete_in(dir, "cd d"), "cd do");
+        assert_eq!(complete_in(dir, "cd doc"), "cd docs/");
+        assert_eq!(complete_in(dir, "cd ."), "cd .hidden/");
+        assert_eq!(complete_in(dir, "touch n"), "touch notes.txt");
+        // Only directories can be changed to
+        assert_eq!(complete_in(dir, "cd n"), "cd n");
+        // Arguments that don't take paths are left alone
+        assert_eq!(complete_in(dir, "sort n"), "sort n");
+    }
+
+    #[test]
+    fn completes_the_argument_that_would_run() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        fs::create_dir(dir.path().join("docs")).expect("docs");
+
+        let dir = dir.path();
+        assert_eq!(complete_in(dir, "cd\tdoc"), "cd docs/");
+        assert_eq!(complete_in(dir, "cd  doc"), "cd docs/");
+    }
+}
@@ -0,0 +1,168 @@
+//! Columns of the long listing, showing what `ls -l` would about each file.
+
+use std::{
+    collections::HashMap,
+    fs::{FileType, Metadata},
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+};
+
+use chrono::{DateTime, Local};
+use users::{get_group_by_gid, get_user_by_uid};
+
+use crate::{app::File, ui::human_size};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detail {
+    Size,
+    Modified,
+    Permissions,
+    /// Owner and group.
+    Owner,
+    /// Where a symlink points, shown after its name.
+    Target,
+}
+
+impl Detail {
+    /// Every column, in the order they are shown in.
+    pub const ALL: [Detail; 5] = [
+        Detail::Permissions,
+        Detail::Owner,
+        Detail::Size,
+        Detail::Modified,
+        Detail::Target,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "size" => Detail::Size,
+            "modified" | "time" => Detail::Modified,
+            "permissions" | "mode" => Detail::Permissions,
+            "owner" => Detail::Owner,
+            "target" | "link" => Detail::Target,
+            _ => return None,
+        })
+    }
+}
+
+/// Adds `detail` to the visible columns or takes it away, keeping them in display order.
+pub fn toggle(details: &mut Vec<Detail>, detail: Detail) {
+    if details.contains(&detail) {
+        details.retain(|d| *d != detail);
+    } else {
+        details.push(detail);
+        details.sort_by_key(|d| Detail::ALL.iter().position(|a| a == d));
+    }
+}
+
+/// User and group names of the ids seen so far, looking them up for every file would be slow.
+#[derive(Debug, Default)]
+pub struct Owners {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Owners {
+    /// Looks up the names of whoever owns `files` that haven't been seen before.
+    pub fn learn<'a>(&mut self, files: impl IntoIterator<Item = &'a File>) {
+        for file in files {
+            let (uid, gid) = (file.metadata.uid(), file.metadata.gid());
+
+            let _ = self.users.entry(uid).or_insert_with(|| {
+                get_user_by_uid(uid)
+                    .map(|u| u.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| uid.to_string())
+            });
+            let _ = self.groups.entry(gid).or_insert_with(|| {
+                get_group_by_gid(gid)
+                    .map(|g| g.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| gid.to_string())
+            });
+        }
+    }
+
+    /// `user:group`, with the ids for owners that haven't been learned.
+    pub fn owner(&self, metadata: &Metadata) -> String {
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        let user = self
+            .users
+            .get(&uid)
+            .cloned()
+            .unwrap_or_else(|| uid.to_string());
+        let group = self
+            .groups
+            .get(&gid)
+            .cloned()
+            .unwrap_or_else(|| gid.to_string());
+
+        format!("{user}:{group}")
+    }
+}
+
+/// The contents of a column for `file`. [`Detail::Target`] isn't a column of its own and is
+/// empty for anything but symlinks.
+pub fn format(detail: Detail, file: &File, owners: &Owners) -> String {
+    let metadata = &file.metadata;
+
+    match detail {
+        // The size of a directory entry says nothing about what is in it
+        Detail::Size if metadata.is_dir() => "-".to_string(),
+        Detail::Size => human_size(metadata.len()),
+        Detail::Modified => match metadata.modified() {
+            Ok(time) => DateTime::<Local>::from(time)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            Err(_) => "-".to_string(),
+        },
+        Detail::Permissions => permissions(metadata),
+        Detail::Owner => owners.owner(metadata),
+        Detail::Target => match &file.link_target {
+            Some(target) => format!(" -> {}", target.display()),
+            None => String::new(),
+        },
+    }
+}
+
+/// Like `ls -l`, e.g. `drwxr-xr-x`.
+fn permissions(metadata: &Metadata) -> String {
+    let mode = metadata.permissions().mode();
+
+    let mut string = String::with_capacity(10);
+    string.push(type_char(metadata.file_type()));
+
+    // Read, write and execute for the owner, group and others. The execute bit shares its
+    // place with setuid, setgid and sticky
+    let specials = [(0o4000, 's'), (0o2000, 's'), (0o1000, 't')];
+    for (i, (special, special_char)) in specials.into_iter().enumerate() {
+        let shift = 6 - i * 3;
+        let bits = mode >> shift;
+
+        string.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+
+    string
+}
+
+fn type_char(file_type: FileType) -> char {
+    if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    }
+}
@@ -0,0 +1,123 @@
+//! Where fls keeps its own files, following the XDG base directory specification.
+
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    fs::{create_dir_all, read_to_string, remove_file, rename, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+/// Handed out to every save in the order they are asked for.
+static NEXT_SAVE: AtomicUsize = AtomicUsize::new(0);
+
+/// The latest save written to each file. Holding the lock is what keeps saves from writing at
+/// the same time.
+static WRITTEN: Lazy<Mutex<HashMap<PathBuf, usize>>> = Lazy::new(Default::default);
+
+pub fn home() -> PathBuf {
+    PathBuf::from(env::var_os("HOME").unwrap_or_default())
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share`.
+pub fn data_home() -> PathBuf {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home().join(".local/share"))
+}
+
+/// Directory for state fls keeps between runs, like the command history.
+pub fn data_dir() -> PathBuf {
+    data_home().join("fls")
+}
+
+/// `$XDG_CONFIG_HOME/fls`, defaulting to `~/.config/fls`.
+pub fn config_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home().join(".config"))
+        .join("fls")
+}
+
+/// Reads `path` as TOML, or the default if there is no such file yet.
+pub async fn load_toml<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    match read_to_string(path).await {
+        Ok(contents) => {
+            toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes `value` to `path` as TOML, like [`save_text`] does. `value` is serialized right away.
+pub fn save_toml(path: PathBuf, value: &impl Serialize) -> impl Future<Output = io::Result<()>> {
+    let save = toml::to_string(value)
+        .map(|contents| save_text(path, contents))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+
+    async move { save?.await }
+}
+
+/// Writes `contents` to `path`, creating the directories leading up to it.
+///
+/// Saves finishing out of order never overwrite a later one. The file is replaced in one go, so
+/// it is never left half written.
+pub fn save_text(path: PathBuf, contents: String) -> impl Future<Output = io::Result<()>> {
+    let save = NEXT_SAVE.fetch_add(1, Ordering::Relaxed);
+
+    async move {
+        let mut written = WRITTEN.lock().await;
+        if written.get(&path).is_some_and(|&latest| latest > save) {
+            return Ok(());
+        }
+
+        write_atomic(&path, contents.as_bytes()).await?;
+        let _ = written.insert(path, save);
+
+        Ok(())
+    }
+}
+
+/// Writes `contents` next to `path` and renames it over `path` once it is all on disk.
+async fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", process::id()));
+    let tmp = path.with_file_name(name);
+
+    let written = async {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp)
+            .await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+
+        rename(&tmp, path).await
+    }
+    .await;
+
+    if written.is_err() {
+        let _ = remove_file(&tmp).await;
+    }
+
+    written
+}
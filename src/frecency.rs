@@ -0,0 +1,414 @@
+//! How often and how recently directories were visited, to jump to them by typing part of
+//! their path like zoxide does. Ranks decay the same way as zoxide's, so an imported database
+//! keeps its order.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{metadata, read, read_to_string};
+
+use crate::{
+    app::{LoadError, SaveError},
+    dirs,
+};
+
+/// Once the ranks add up to more than this, they are all scaled down and the least visited
+/// directories forgotten.
+const MAX_TOTAL_RANK: f64 = 10_000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Visits {
+    pub dir: PathBuf,
+    /// How many times `dir` was visited, decayed as the database ages.
+    pub rank: f64,
+    /// Seconds since the epoch.
+    pub last_visit: u64,
+}
+
+impl Visits {
+    /// The rank, weighted by how long ago the last visit was.
+    fn score(&self, now: u64) -> f64 {
+        const HOUR: u64 = 60 * 60;
+
+        let factor = match now.saturating_sub(self.last_visit) {
+            age if age < HOUR => 4.0,
+            age if age < 24 * HOUR => 2.0,
+            age if age < 7 * 24 * HOUR => 0.5,
+            _ => 0.25,
+        };
+
+        self.rank * factor
+    }
+}
+
+/// Databases of other tools that can be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    Zoxide,
+    Autojump,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Frecency {
+    #[serde(default, rename = "dir")]
+    dirs: Vec<Visits>,
+    /// The database couldn't be read, visits aren't written over it.
+    #[serde(skip)]
+    unreadable: bool,
+}
+
+impl Frecency {
+    fn path() -> PathBuf {
+        dirs::data_dir().join("frecency.toml")
+    }
+
+    /// Reads the database, or why it couldn't be. Visits are then only counted until fls
+    /// quits, a database that can't be read isn't overwritten.
+    pub async fn load() -> (Frecency, Option<LoadError>) {
+        let path = Self::path();
+        match dirs::load_toml(&path).await {
+            Ok(frecency) => (frecency, None),
+            Err(e) => {
+                let frecency = Frecency {
+                    unreadable: true,
+                    ..Frecency::default()
+                };
+                let error = LoadError {
+                    path,
+                    reason: e.to_string(),
+                };
+                (frecency, Some(error))
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    pub fn visit(&mut self, dir: &Path) {
+        let now = now();
+
+        match self.dirs.iter_mut().find(|v| v.dir == dir) {
+            Some(visits) => {
+                visits.rank += 1.0;
+                visits.last_visit = now;
+            }
+            None => self.dirs.push(Visits {
+                dir: dir.to_path_buf(),
+                rank: 1.0,
+                last_visit: now,
+            }),
+        }
+
+        self.age();
+    }
+
+    /// Adds the visits in `other`, e.g. an imported database or visits made before this one
+    /// was loaded.
+    pub fn merge(&mut self, other: Frecency) {
+        for visits in other.dirs {
+            match self.dirs.iter_mut().find(|v| v.dir == visits.dir) {
+                Some(existing) => {
+                    existing.rank += visits.rank;
+                    existing.last_visit = existing.last_visit.max(visits.last_visit);
+                }
+                None => self.dirs.push(visits),
+            }
+        }
+
+        self.age();
+    }
+
+    pub fn remove(&mut self, dir: &Path) {
+        self.dirs.retain(|v| v.dir != dir);
+    }
+
+    /// Directories whose path fuzzy matches `query`, the most frecent first.
+    pub fn matching(&self, query: &str) -> Vec<&Visits> {
+        let matcher = SkimMatcherV2::default();
+        let now = now();
+
+        let mut matches: Vec<_> = self
+            .dirs
+            .iter()
+            .filter(|v| {
+                query.is_empty()
+                    || matcher
+                        .fuzzy_match(&v.dir.to_string_lossy(), query)
+                        .is_some()
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)));
+        matches
+    }
+
+    /// Scales the ranks down once they grow too large, dropping whatever falls below one
+    /// visit.
+    fn age(&mut self) {
+        let total: f64 = self.dirs.iter().map(|v| v.rank).sum();
+        if total <= MAX_TOTAL_RANK {
+            return;
+        }
+
+        let factor = 0.9 * MAX_TOTAL_RANK / total;
+        for visits in &mut self.dirs {
+            visits.rank *= factor;
+        }
+        self.dirs.retain(|v| v.rank >= 1.0);
+    }
+
+    /// Whether the database on disk couldn't be read, so nothing is saved to it.
+    pub fn is_unreadable(&self) -> bool {
+        self.unreadable
+    }
+
+    /// Writes the database to disk.
+    pub fn save(&self) -> impl std::future::Future<Output = Result<(), SaveError>> {
+        let path = Self::path();
+        let save = (!self.unreadable).then(|| dirs::save_toml(path.clone(), self));
+        async move {
+            match save {
+                Some(save) => save.await.map_err(|e| SaveError {
+                    path,
+                    reason: e.to_string(),
+                }),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Where `source` keeps its database by default.
+fn default_import_path(source: ImportSource) -> PathBuf {
+    match source {
+        ImportSource::Zoxide => env::var_os("_ZO_DATA_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| dirs::data_home().join("zoxide"))
+            .join("db.zo"),
+        ImportSource::Autojump => dirs::data_home().join("autojump/autojump.txt"),
+    }
+}
+
+/// Reads the database of `source` at `path`, or where it is kept by default.
+pub async fn import(source: ImportSource, path: Option<PathBuf>) -> Result<Frecency, ImportError> {
+    let path = path.unwrap_or_else(|| default_import_path(source));
+    let error = |reason: String| ImportError {
+        path: path.clone(),
+        reason,
+    };
+
+    let dirs = match source {
+        ImportSource::Zoxide => {
+            let bytes = read(&path).await.map_err(|e| error(e.to_string()))?;
+            parse_zoxide(&bytes).map_err(error)?
+        }
+        ImportSource::Autojump => {
+            let contents = read_to_string(&path)
+                .await
+                .map_err(|e| error(e.to_string()))?;
+
+            // Autojump doesn't keep track of when a directory was visited, the last time its
+            // database was written is the best guess there is
+            let written = metadata(&path)
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+
+            parse_autojump(&contents, written).map_err(error)?
+        }
+    };
+
+    Ok(Frecency {
+        dirs,
+        ..Frecency::default()
+    })
+}
+
+/// Autojump's `autojump.txt`, one `weight<TAB>path` per line.
+fn parse_autojump(contents: &str, last_visit: u64) -> Result<Vec<Visits>, String> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            let (weight, dir) = line
+                .split_once('\t')
+                .ok_or_else(|| format!("line {} has no tab", i + 1))?;
+            let rank = weight
+                .parse()
+                .map_err(|_| format!("line {} has no weight", i + 1))?;
+
+            Ok(Visits {
+                dir: PathBuf::from(dir),
+                rank,
+                last_visit,
+            })
+        })
+        .collect()
+}
+
+/// Zoxide's `db.zo`, which is bincode: a `u32` version, then the directories as a `u64`
+/// length followed by each path, rank and last access time. Only version 3 is understood.
+fn parse_zoxide(mut bytes: &[u8]) -> Result<Vec<Visits>, String> {
+    let version = u32::from_le_bytes(take(&mut bytes)?);
+    if version != 3 {
+        return Err(format!("unsupported zoxide database version {version}"));
+    }
+
+    let count = u64::from_le_bytes(take(&mut bytes)?);
+    let mut dirs = vec![];
+    for _ in 0..count {
+        let len = u64::from_le_bytes(take(&mut bytes)?) as usize;
+        if bytes.len() < len {
+            return Err("database is cut off".to_string());
+        }
+        let (dir, rest) = bytes.split_at(len);
+        bytes = rest;
+
+        let dir = std::str::from_utf8(dir).map_err(|e| e.to_string())?;
+        let rank = f64::from_le_bytes(take(&mut bytes)?);
+        let last_visit = u64::from_le_bytes(take(&mut bytes)?);
+
+        dirs.push(Visits {
+            dir: PathBuf::from(dir),
+            rank,
+            last_visit,
+        });
+    }
+
+    Ok(dirs)
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], String> {
+    if bytes.len() < N {
+        return Err("database is cut off".to_string());
+    }
+
+    let (taken, rest) = bytes.split_at(N);
+    *bytes = rest;
+    Ok(taken.try_into().expect("split at N"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zoxide(version: u32, dirs: &[(&str, f64, u64)]) -> Vec<u8> {
+        let mut bytes = version.to_le_bytes().to_vec();
+        bytes.extend((dirs.len() as u64).to_le_bytes());
+        for (dir, rank, last_visit) in dirs {
+            bytes.extend((dir.len() as u64).to_le_bytes());
+            bytes.extend(dir.as_bytes());
+            bytes.extend(rank.to_le_bytes());
+            bytes.extend(last_visit.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    fn visits(dir: &str, rank: f64) -> Visits {
+        Visits {
+            dir: PathBuf::from(dir),
+            rank,
+            last_visit: 0,
+        }
+    }
+
+    #[test]
+    fn parses_zoxide() {
+        let bytes = zoxide(3, &[("/home/me", 12.5, 100), ("/tmp", 1.0, 200)]);
+        assert_eq!(
+            parse_zoxide(&bytes),
+            Ok(vec![
+                Visits {
+                    dir: PathBuf::from("/home/me"),
+                    rank: 12.5,
+                    last_visit: 100,
+                },
+                Visits {
+                    dir: PathBuf::from("/tmp"),
+                    rank: 1.0,
+                    last_visit: 200,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_empty_zoxide() {
+        assert_eq!(parse_zoxide(&zoxide(3, &[])), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejects_other_zoxide_versions() {
+        assert!(parse_zoxide(&zoxide(2, &[("/a", 1.0, 0)])).is_err());
+    }
+
+    #[test]
+    fn rejects_cut_off_zoxide() {
+        let bytes = zoxide(3, &[("/home/me", 1.0, 100)]);
+        for len in 0..bytes.len() {
+            assert!(parse_zoxide(&bytes[..len]).is_err(), "cut at {len}");
+        }
+    }
+
+    #[test]
+    fn rejects_zoxide_with_a_huge_length() {
+        let mut bytes = zoxide(3, &[]);
+        bytes[4..12].copy_from_slice(&1u64.to_le_bytes());
+        bytes.extend(u64::MAX.to_le_bytes());
+        assert!(parse_zoxide(&bytes).is_err());
+    }
+
+    #[test]
+    fn small_databases_dont_age() {
+        let mut frecency = Frecency {
+            dirs: vec![visits("/a", 5000.0), visits("/b", 5000.0)],
+            ..Frecency::default()
+        };
+        frecency.age();
+        assert_eq!(frecency.dirs[0].rank, 5000.0);
+        assert_eq!(frecency.dirs[1].rank, 5000.0);
+    }
+
+    #[test]
+    fn aging_scales_ranks_and_forgets_rare_dirs() {
+        let mut frecency = Frecency {
+            dirs: vec![visits("/a", 19_999.0), visits("/b", 1.0)],
+            ..Frecency::default()
+        };
+        frecency.age();
+
+        // Scaled to 90% of the maximum, which leaves /b below one visit
+        assert_eq!(frecency.len(), 1);
+        assert_eq!(frecency.dirs[0].dir, PathBuf::from("/a"));
+        assert!((frecency.dirs[0].rank - 0.9 * 19_999.0 / 2.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,102 @@
+//! Syntax highlighting for text previews. Text is split into spans of a [`Highlight`] kind, the
+//! colors for those come from the [`crate::theme::Theme`] when drawing.
+
+use once_cell::sync::Lazy;
+use syntect::{
+    parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Plain,
+    Comment,
+    String,
+    /// Numbers, `true`, character escapes and the like.
+    Constant,
+    Keyword,
+    Type,
+    Function,
+}
+
+/// Scope prefixes and what they are highlighted as, the first match wins.
+const SCOPES: &[(&str, Highlight)] = &[
+    ("comment", Highlight::Comment),
+    ("string", Highlight::String),
+    ("constant", Highlight::Constant),
+    ("entity.name.type", Highlight::Type),
+    ("support.type", Highlight::Type),
+    ("entity.name.function", Highlight::Function),
+    ("support.function", Highlight::Function),
+    ("keyword", Highlight::Keyword),
+    ("storage", Highlight::Keyword),
+];
+
+pub type Line = Vec<(Highlight, String)>;
+
+/// Splits `text` into highlighted lines. The syntax is picked by the extension or name of
+/// `file_name`, or by the first line for things like shebangs.
+pub fn highlight(file_name: &str, text: &str) -> Vec<Line> {
+    let syntax = syntax(file_name, text);
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+
+    let mut lines = vec![];
+    for line in LinesWithEndings::from(text) {
+        // Nothing sensible can come after a parsing error, the rest is left plain
+        let ops = state.parse_line(line, &SYNTAXES).unwrap_or_default();
+
+        let mut spans = vec![];
+        let mut start = 0;
+        for (end, op) in ops {
+            push_span(&mut spans, &stack, &line[start..end]);
+            let _ = stack.apply(&op);
+            start = end;
+        }
+        push_span(&mut spans, &stack, &line[start..]);
+
+        lines.push(spans);
+    }
+
+    lines
+}
+
+fn syntax<'a>(file_name: &str, text: &str) -> &'a SyntaxReference {
+    let extension = file_name.rsplit_once('.').map(|(_, ext)| ext);
+    let first_line = text.lines().next().unwrap_or_default();
+
+    extension
+        .and_then(|ext| SYNTAXES.find_syntax_by_extension(ext))
+        // Some syntaxes list whole names, like `Makefile`
+        .or_else(|| SYNTAXES.find_syntax_by_extension(file_name))
+        .or_else(|| SYNTAXES.find_syntax_by_first_line(first_line))
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text())
+}
+
+fn push_span(spans: &mut Line, stack: &ScopeStack, text: &str) {
+    let text = text.trim_end_matches(&['\n', '\r'][..]);
+    if text.is_empty() {
+        return;
+    }
+
+    let kind = stack
+        .as_slice()
+        .iter()
+        .rev()
+        .find_map(|scope| {
+            let name = scope.build_string();
+            SCOPES
+                .iter()
+                .find(|(prefix, _)| name.starts_with(prefix))
+                .map(|(_, kind)| *kind)
+        })
+        .unwrap_or(Highlight::Plain);
+
+    // Neighbours of the same kind are drawn as one
+    match spans.last_mut() {
+        Some((last, contents)) if *last == kind => contents.push_str(text),
+        _ => spans.push((kind, text.to_string())),
+    }
+}
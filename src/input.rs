@@ -0,0 +1,67 @@
+//! Single line text editing for prompts typed into the file list or the bottom bar.
+
+/// An edit to a [`LineInput`], produced from key presses by [`crate::mode::Mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Insert(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LineInput {
+    pub text: String,
+    /// Byte offset into `text`, always on a char boundary.
+    pub cursor: usize,
+}
+
+impl LineInput {
+    pub fn new(text: String, cursor: usize) -> Self {
+        let cursor = cursor.min(text.len());
+        Self { text, cursor }
+    }
+
+    pub fn apply(&mut self, edit: Edit) {
+        match edit {
+            Edit::Insert(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            Edit::Backspace => {
+                if let Some(prev) = self.prev_boundary() {
+                    let _ = self.text.remove(prev);
+                    self.cursor = prev;
+                }
+            }
+            Edit::Delete => {
+                if self.cursor < self.text.len() {
+                    let _ = self.text.remove(self.cursor);
+                }
+            }
+            Edit::Left => self.cursor = self.prev_boundary().unwrap_or(0),
+            Edit::Right => {
+                if let Some(c) = self.text[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                }
+            }
+            Edit::Home => self.cursor = 0,
+            Edit::End => self.cursor = self.text.len(),
+        }
+    }
+
+    /// Text before and after the cursor.
+    pub fn split(&self) -> (&str, &str) {
+        self.text.split_at(self.cursor)
+    }
+
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .char_indices()
+            .last()
+            .map(|(i, _)| i)
+    }
+}
@@ -0,0 +1,240 @@
+//! Background jobs for file operations that can take a while, with progress and cancellation.
+
+use std::{
+    any::TypeId,
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use iced::{futures::future::pending, Subscription};
+use iced_native::subscription::unfold;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    app::{Message, Register, RegisterKind, TrashError},
+    journal::{revert_batch, Direction, Operation},
+    tasks::{self, count_items, trash_files, ConflictPolicy},
+    trash,
+};
+
+pub type JobId = usize;
+
+/// How often a running job reports progress at most.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Long running filesystem work, run in the background so progress can be shown while it runs.
+#[derive(Debug, Clone)]
+pub enum JobKind {
+    Trash(Vec<PathBuf>),
+    Paste {
+        register: Register,
+        dest: PathBuf,
+        policy: ConflictPolicy,
+    },
+    Revert(Direction, Vec<Operation>),
+    EmptyTrash,
+}
+
+impl JobKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            JobKind::Trash(_) => "Deleting",
+            JobKind::Paste { register, .. } => match register.kind {
+                RegisterKind::Yank => "Copying",
+                RegisterKind::Cut => "Moving",
+            },
+            JobKind::Revert(Direction::Undo, _) => "Undoing",
+            JobKind::Revert(Direction::Redo, _) => "Redoing",
+            JobKind::EmptyTrash => "Emptying trash",
+        }
+    }
+
+    /// Does the work, returning the message to handle once it is done.
+    async fn run(self, ctx: &mut JobContext) -> Message {
+        match self {
+            JobKind::Trash(paths) => {
+                ctx.set_total(paths.len(), 0);
+                let (ops, errors) = trash_files(paths, ctx).await;
+                Message::FileDeleteResult(ops, errors)
+            }
+            JobKind::Paste {
+                register,
+                dest,
+                policy,
+            } => {
+                // Only copies move bytes around for sure, moves are usually a rename
+                let bytes = match register.kind {
+                    RegisterKind::Yank => count_items(register.paths.clone()).await.1,
+                    RegisterKind::Cut => 0,
+                };
+                ctx.set_total(register.paths.len(), bytes);
+
+                let (ops, errors) =
+                    tasks::paste(register.paths, dest, register.kind, policy, ctx).await;
+                Message::Pasted(ops, errors)
+            }
+            JobKind::Revert(direction, batch) => {
+                ctx.set_total(batch.len(), 0);
+                Message::Reverted(direction, revert_batch(batch, ctx).await)
+            }
+            JobKind::EmptyTrash => Message::TrashResult(
+                trash::empty(ctx)
+                    .await
+                    .map_err(|e| TrashError(e.to_string())),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+    pub items_done: usize,
+    pub items_total: usize,
+    pub bytes_done: u64,
+    /// 0 when the amount of bytes to move isn't known up front.
+    pub bytes_total: u64,
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub progress: Progress,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn new(id: JobId, kind: JobKind) -> Self {
+        Self {
+            id,
+            kind,
+            progress: Progress::default(),
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Asks the job to stop after whatever it is in the middle of. Work already done is kept.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Runs the job, streaming `JobProgress` while it runs and `JobFinished` once it is done.
+    pub fn subscription(&self) -> Subscription<Message> {
+        struct JobRunner;
+
+        let id = self.id;
+
+        unfold(
+            (TypeId::of::<JobRunner>(), id),
+            State::Start(self.kind.clone(), self.cancel.clone()),
+            move |state| async move {
+                match state {
+                    State::Start(kind, cancel) => {
+                        let (tx, rx) = unbounded_channel();
+                        let mut ctx = JobContext {
+                            progress: Progress::default(),
+                            updates: Some(tx.clone()),
+                            cancel,
+                            last_update: None,
+                        };
+
+                        // Detached, the job keeps going even if nothing is listening anymore
+                        drop(tokio::spawn(async move {
+                            let message = kind.run(&mut ctx).await;
+                            let _ = tx.send(Update::Done(message));
+                        }));
+
+                        (None, State::Running(rx))
+                    }
+                    State::Running(mut rx) => match rx.recv().await {
+                        Some(Update::Progress(p)) => {
+                            (Some(Message::JobProgress(id, p)), State::Running(rx))
+                        }
+                        Some(Update::Done(m)) => {
+                            (Some(Message::JobFinished(id, Box::new(m))), State::Finished)
+                        }
+                        None => (None, State::Finished),
+                    },
+                    State::Finished => pending().await,
+                }
+            },
+        )
+    }
+}
+
+enum State {
+    Start(JobKind, Arc<AtomicBool>),
+    Running(UnboundedReceiver<Update>),
+    Finished,
+}
+
+#[derive(Debug)]
+enum Update {
+    Progress(Progress),
+    Done(Message),
+}
+
+/// Handed down to filesystem tasks so they can report progress and notice cancellation. The
+/// default context reports to nobody and is never cancelled.
+#[derive(Debug, Default)]
+pub struct JobContext {
+    progress: Progress,
+    updates: Option<UnboundedSender<Update>>,
+    cancel: Arc<AtomicBool>,
+    last_update: Option<Instant>,
+}
+
+impl JobContext {
+    /// Errors out once the job has been cancelled, meant to be `?`'d between units of work.
+    pub fn check(&self) -> io::Result<()> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_total(&mut self, items: usize, bytes: u64) {
+        self.progress.items_total = items;
+        self.progress.bytes_total = bytes;
+        self.send(true);
+    }
+
+    /// One of the paths the job was started with is done.
+    pub fn item_done(&mut self) {
+        self.progress.items_done += 1;
+        self.send(false);
+    }
+
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.progress.bytes_done += bytes;
+        self.send(false);
+    }
+
+    fn send(&mut self, force: bool) {
+        let updates = match &self.updates {
+            Some(updates) => updates,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let due = match self.last_update {
+            Some(last) => now.duration_since(last) >= UPDATE_INTERVAL,
+            None => true,
+        };
+
+        if force || due {
+            self.last_update = Some(now);
+            let _ = updates.send(Update::Progress(self.progress));
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::{io, path::PathBuf};
+
+use crate::{
+    jobs::JobContext,
+    tasks::move_path,
+    trash::{self, TrashEntry},
+};
+
+/// A change made to the filesystem, recorded so it can be reverted.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// A file was moved into the trash.
+    Trashed(TrashEntry),
+    /// A file was renamed or moved.
+    Renamed { from: PathBuf, to: PathBuf },
+    /// A file was created, copied or restored from the trash.
+    Created(PathBuf),
+}
+
+impl Operation {
+    /// Reverts the operation, returning the operation that reverts the revert.
+    pub async fn revert(self, ctx: &mut JobContext) -> io::Result<Operation> {
+        match self {
+            Operation::Trashed(entry) => trash::restore(entry, ctx).await.map(Operation::Created),
+            Operation::Renamed { from, to } => {
+                move_path(&to, &from, ctx).await?;
+                Ok(Operation::Renamed { from: to, to: from })
+            }
+            // Nothing is ever deleted for good, created files go to the trash
+            Operation::Created(path) => trash::trash(path, ctx).await.map(Operation::Trashed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Undo,
+    Redo,
+}
+
+/// Outcome of reverting a batch of operations.
+#[derive(Debug, Clone)]
+pub struct Reverted {
+    /// Inverses of the operations that were reverted, in the order they were reverted.
+    pub done: Vec<Operation>,
+    /// Operations that were not reverted because an earlier one failed.
+    pub remaining: Vec<Operation>,
+    pub error: Option<String>,
+}
+
+/// Reverts a batch last operation first, stopping at the first failure.
+pub async fn revert_batch(mut batch: Vec<Operation>, ctx: &mut JobContext) -> Reverted {
+    let mut done = vec![];
+
+    while let Some(op) = batch.pop() {
+        let result = match ctx.check() {
+            Ok(()) => op.clone().revert(ctx).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(inverse) => {
+                done.push(inverse);
+                ctx.item_done();
+            }
+            Err(e) => {
+                batch.push(op);
+                return Reverted {
+                    done,
+                    remaining: batch,
+                    error: Some(e.to_string()),
+                };
+            }
+        }
+    }
+
+    Reverted {
+        done,
+        remaining: batch,
+        error: None,
+    }
+}
+
+/// Undo and redo stacks. Every entry is a batch of operations made by a single action, so
+/// undoing a bulk delete brings back every file in it.
+#[derive(Debug, Default)]
+pub struct Journal {
+    undo: Vec<Vec<Operation>>,
+    redo: Vec<Vec<Operation>>,
+}
+
+impl Journal {
+    /// Records a new batch, which invalidates anything that could be redone.
+    pub fn record(&mut self, batch: Vec<Operation>) {
+        if !batch.is_empty() {
+            self.undo.push(batch);
+            self.redo.clear();
+        }
+    }
+
+    pub fn take(&mut self, direction: Direction) -> Option<Vec<Operation>> {
+        match direction {
+            Direction::Undo => self.undo.pop(),
+            Direction::Redo => self.redo.pop(),
+        }
+    }
+
+    /// Puts the result of reverting a batch taken with [`Journal::take`] back on the stacks.
+    pub fn finish(&mut self, direction: Direction, reverted: Reverted) {
+        let (from, to) = match direction {
+            Direction::Undo => (&mut self.undo, &mut self.redo),
+            Direction::Redo => (&mut self.redo, &mut self.undo),
+        };
+
+        if !reverted.remaining.is_empty() {
+            from.push(reverted.remaining);
+        }
+
+        // `done` ends with the inverse of the first operation of the batch, and reverting pops
+        // from the end, so going back the other way replays the batch in its original order
+        if !reverted.done.is_empty() {
+            to.push(reverted.done);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamed(from: &str, to: &str) -> Operation {
+        Operation::Renamed {
+            from: PathBuf::from(from),
+            to: PathBuf::from(to),
+        }
+    }
+
+    fn renames(batch: &[Operation]) -> Vec<(&str, &str)> {
+        batch
+            .iter()
+            .map(|op| match op {
+                Operation::Renamed { from, to } => (from.to_str().unwrap(), to.to_str().unwrap()),
+                _ => panic!("not a rename: {op:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undone_batches_can_be_redone() {
+        let mut journal = Journal::default();
+        journal.record(vec![renamed("/a", "/b")]);
+        journal.record(vec![renamed("/c", "/d"), renamed("/e", "/f")]);
+
+        let batch = journal.take(Direction::Undo).unwrap();
+        assert_eq!(renames(&batch), [("/c", "/d"), ("/e", "/f")]);
+
+        // Reverting pops from the end, so the inverses come last operation first
+        let reverted = Reverted {
+            done: vec![renamed("/f", "/e"), renamed("/d", "/c")],
+            remaining: vec![],
+            error: None,
+        };
+        journal.finish(Direction::Undo, reverted);
+
+        let redo = journal.take(Direction::Redo).unwrap();
+        assert_eq!(renames(&redo), [("/f", "/e"), ("/d", "/c")]);
+        assert_eq!(
+            renames(&journal.take(Direction::Undo).unwrap()),
+            [("/a", "/b")]
+        );
+        assert!(journal.take(Direction::Undo).is_none());
+    }
+
+    #[test]
+    fn new_operations_clear_redo() {
+        let mut journal = Journal::default();
+        journal.record(vec![renamed("/a", "/b")]);
+        let _ = journal.take(Direction::Undo).unwrap();
+        journal.finish(
+            Direction::Undo,
+            Reverted {
+                done: vec![renamed("/b", "/a")],
+                remaining: vec![],
+                error: None,
+            },
+        );
+
+        journal.record(vec![]);
+        assert_eq!(journal.redo.len(), 1, "empty batches aren't recorded");
+
+        journal.record(vec![renamed("/c", "/d")]);
+        assert!(journal.take(Direction::Redo).is_none());
+    }
+
+    #[test]
+    fn partly_reverted_batches_stay_on_both_stacks() {
+        let mut journal = Journal::default();
+        journal.record(vec![renamed("/a", "/b"), renamed("/c", "/d")]);
+        let _ = journal.take(Direction::Undo).unwrap();
+        journal.finish(
+            Direction::Undo,
+            Reverted {
+                done: vec![renamed("/d", "/c")],
+                remaining: vec![renamed("/a", "/b")],
+                error: Some("gone".to_string()),
+            },
+        );
+
+        assert_eq!(
+            renames(&journal.take(Direction::Redo).unwrap()),
+            [("/d", "/c")]
+        );
+        assert_eq!(
+            renames(&journal.take(Direction::Undo).unwrap()),
+            [("/a", "/b")]
+        );
+    }
+
+    #[test]
+    fn revert_batch_stops_at_the_first_failure() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        for name in ["b", "f"] {
+            std::fs::write(path(name), "").unwrap();
+        }
+
+        // Reverted last first: "f" goes back, "d" is missing, "b" is never tried
+        let batch = vec![
+            renamed(&path("a"), &path("b")),
+            renamed(&path("c"), &path("d")),
+            renamed(&path("e"), &path("f")),
+        ];
+        let reverted = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime")
+            .block_on(revert_batch(batch, &mut JobContext::default()));
+
+        assert_eq!(renames(&reverted.done), [(&*path("f"), &*path("e"))]);
+        assert_eq!(
+            renames(&reverted.remaining),
+            [(&*path("a"), &*path("b")), (&*path("c"), &*path("d"))]
+        );
+        assert!(reverted.error.is_some());
+        assert!(dir.path().join("e").exists());
+        assert!(dir.path().join("b").exists());
+    }
+}
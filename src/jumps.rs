@@ -0,0 +1,193 @@
+//! Directories visited before, to go back and forth between like vim's jumplist.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// How many directories are kept to go back to.
+const MAX_JUMPS: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct Jumplist {
+    back: Vec<PathBuf>,
+    forward: Vec<PathBuf>,
+    /// File last hovered in each directory that was left, hovered again on coming back.
+    hovered: HashMap<PathBuf, PathBuf>,
+}
+
+impl Jumplist {
+    /// Records leaving `dir` for somewhere new, with `hovered` being hovered in it. Like in a
+    /// browser, whatever could be gone forward to is forgotten.
+    pub fn leave(&mut self, dir: &Path, hovered: Option<PathBuf>) {
+        self.remember_hovered(dir, hovered);
+        self.forward.clear();
+
+        if self.back.last().map(PathBuf::as_path) != Some(dir) {
+            self.back.push(dir.to_path_buf());
+        }
+        if self.back.len() > MAX_JUMPS {
+            let _ = self.back.remove(0);
+        }
+    }
+
+    /// The directory `steps` back from `dir`, or as far back as there is.
+    pub fn back(&mut self, dir: &Path, hovered: Option<PathBuf>, steps: usize) -> Option<PathBuf> {
+        let target = step(&mut self.back, &mut self.forward, dir, steps)?;
+        self.remember_hovered(dir, hovered);
+
+        Some(target)
+    }
+
+    /// The directory `steps` forward from `dir`, or as far forward as there is.
+    pub fn forward(
+        &mut self,
+        dir: &Path,
+        hovered: Option<PathBuf>,
+        steps: usize,
+    ) -> Option<PathBuf> {
+        let target = step(&mut self.forward, &mut self.back, dir, steps)?;
+        self.remember_hovered(dir, hovered);
+
+        Some(target)
+    }
+
+    /// Directories that going back would pass through, nearest last.
+    pub fn back_dirs(&self) -> &[PathBuf] {
+        &self.back
+    }
+
+    /// Directories that going forward would pass through, nearest last.
+    pub fn forward_dirs(&self) -> &[PathBuf] {
+        &self.forward
+    }
+
+    /// Drops `dirs` from both directions, for directories removed since they were visited.
+    pub fn forget(&mut self, dirs: &[PathBuf]) {
+        self.back.retain(|d| !dirs.contains(d));
+        self.forward.retain(|d| !dirs.contains(d));
+        self.hovered.retain(|d, _| !dirs.contains(d));
+    }
+
+    /// File that was hovered when `dir` was last left.
+    pub fn hovered(&self, dir: &Path) -> Option<&PathBuf> {
+        self.hovered.get(dir)
+    }
+
+    fn remember_hovered(&mut self, dir: &Path, hovered: Option<PathBuf>) {
+        if let Some(hovered) = hovered {
+            let _ = self.hovered.insert(dir.to_path_buf(), hovered);
+        }
+    }
+}
+
+/// Pops up to `steps` directories off `from`, pushing the ones passed through onto `to`.
+fn step(
+    from: &mut Vec<PathBuf>,
+    to: &mut Vec<PathBuf>,
+    dir: &Path,
+    steps: usize,
+) -> Option<PathBuf> {
+    let mut current = dir.to_path_buf();
+    let mut moved = false;
+
+    for _ in 0..steps {
+        let next = match from.pop() {
+            Some(next) => next,
+            None => break,
+        };
+
+        to.push(std::mem::replace(&mut current, next));
+        moved = true;
+    }
+
+    if moved {
+        Some(current)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visit(jumps: &mut Jumplist, dirs: &[&str]) {
+        for pair in dirs.windows(2) {
+            jumps.leave(Path::new(pair[0]), None);
+        }
+    }
+
+    #[test]
+    fn back_and_forward_retrace_the_visits() {
+        let mut jumps = Jumplist::default();
+        visit(&mut jumps, &["/a", "/b", "/c", "/d"]);
+
+        let d = Path::new("/d");
+        assert_eq!(jumps.back(d, None, 2), Some(PathBuf::from("/b")));
+        assert_eq!(jumps.back_dirs(), [PathBuf::from("/a")]);
+        assert_eq!(
+            jumps.forward(Path::new("/b"), None, 1),
+            Some(PathBuf::from("/c"))
+        );
+        assert_eq!(
+            jumps.forward(Path::new("/c"), None, 5),
+            Some(PathBuf::from("/d"))
+        );
+        assert_eq!(jumps.forward(d, None, 1), None);
+    }
+
+    #[test]
+    fn going_somewhere_new_forgets_forward() {
+        let mut jumps = Jumplist::default();
+        visit(&mut jumps, &["/a", "/b", "/c"]);
+
+        assert_eq!(
+            jumps.back(Path::new("/c"), None, 1),
+            Some(PathBuf::from("/b"))
+        );
+        jumps.leave(Path::new("/b"), None);
+        assert!(jumps.forward_dirs().is_empty());
+        assert_eq!(
+            jumps.back_dirs(),
+            [PathBuf::from("/a"), PathBuf::from("/b")]
+        );
+    }
+
+    #[test]
+    fn only_the_latest_jumps_are_kept() {
+        let mut jumps = Jumplist::default();
+        for i in 0..MAX_JUMPS + 10 {
+            jumps.leave(Path::new(&format!("/{i}")), None);
+        }
+
+        assert_eq!(jumps.back_dirs().len(), MAX_JUMPS);
+        assert_eq!(jumps.back_dirs()[0], PathBuf::from("/10"));
+    }
+
+    #[test]
+    fn forgotten_dirs_are_stepped_over() {
+        let mut jumps = Jumplist::default();
+        visit(&mut jumps, &["/a", "/gone", "/b", "/gone", "/c"]);
+
+        jumps.forget(&[PathBuf::from("/gone")]);
+        assert_eq!(
+            jumps.back(Path::new("/c"), None, 2),
+            Some(PathBuf::from("/a"))
+        );
+        assert_eq!(
+            jumps.forward_dirs(),
+            [PathBuf::from("/c"), PathBuf::from("/b")]
+        );
+    }
+
+    #[test]
+    fn hovered_file_is_remembered_per_dir() {
+        let mut jumps = Jumplist::default();
+        jumps.leave(Path::new("/a"), Some(PathBuf::from("/a/x")));
+        let _ = jumps.back(Path::new("/b"), Some(PathBuf::from("/b/y")), 1);
+
+        assert_eq!(jumps.hovered(Path::new("/a")), Some(&PathBuf::from("/a/x")));
+        assert_eq!(jumps.hovered(Path::new("/b")), Some(&PathBuf::from("/b/y")));
+    }
+}
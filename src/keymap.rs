@@ -0,0 +1,834 @@
+//! Key bindings, loaded from `$XDG_CONFIG_HOME/fls/keymap.toml` on top of a built-in preset.
+//!
+//! ```toml
+//! preset = "qwerty"
+//!
+//! [normal]
+//! C-d = "delete"
+//! d = "none"
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, io,
+    time::Duration,
+};
+
+use iced::keyboard::Modifiers;
+use iced_native::keyboard::KeyCode;
+use serde::Deserialize;
+use tokio::fs::read_to_string;
+
+use crate::{
+    app::{Action, BookmarksView, SettingsView, TrashView, View},
+    details::Detail,
+    dirs,
+    input::Edit,
+    mode::{Mode, SearchMode, DEFAULT_GLOBAL_DEPTH},
+    sort::SortKey,
+    tasks::{ConflictPolicy, CreateKind},
+};
+
+/// How long a key that both is bound and starts a longer sequence waits for the rest of it.
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+const COLEMAK: &str = include_str!("../keymaps/colemak.toml");
+const QWERTY: &str = include_str!("../keymaps/qwerty.toml");
+
+/// Group of bindings that is active at once. Every [`Mode`] reads from one section, the text
+/// prompts all share [`Section::Prompt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    Normal,
+    Visual,
+    Search,
+    ConfirmDelete,
+    ResolveConflict,
+    Prompt,
+    /// Waiting for the letter of a mark.
+    Mark,
+    /// The trash view.
+    Trash,
+}
+
+impl Section {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "normal" => Section::Normal,
+            "visual" => Section::Visual,
+            "search" => Section::Search,
+            "confirm_delete" => Section::ConfirmDelete,
+            "resolve_conflict" => Section::ResolveConflict,
+            "prompt" => Section::Prompt,
+            "mark" => Section::Mark,
+            "trash" => Section::Trash,
+            _ => return None,
+        })
+    }
+}
+
+/// A key along with the modifiers held down with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    key_code: KeyCode,
+    modifiers: Modifiers,
+}
+
+impl Chord {
+    pub fn new(key_code: KeyCode, modifiers: Modifiers) -> Self {
+        // Some platforms report `:` as its own key, others as shift+;
+        match key_code {
+            KeyCode::Colon => Self {
+                key_code: KeyCode::Semicolon,
+                modifiers: modifiers | Modifiers::SHIFT,
+            },
+            key_code => Self {
+                key_code,
+                modifiers,
+            },
+        }
+    }
+
+    /// Parses chords like `a`, `A`, `C-r`, `S-tab` or `esc`.
+    fn parse(chord: &str) -> Option<Self> {
+        let mut modifiers = Modifiers::empty();
+        let mut parts: Vec<_> = chord.split('-').collect();
+        let key = parts.pop()?;
+
+        for modifier in parts {
+            modifiers = modifiers
+                | match modifier {
+                    "C" | "ctrl" => Modifiers::CTRL,
+                    "S" | "shift" => Modifiers::SHIFT,
+                    "A" | "alt" => Modifiers::ALT,
+                    "M" | "logo" => Modifiers::LOGO,
+                    _ => return None,
+                };
+        }
+
+        let mut chars = key.chars();
+        let key_code = match (chars.next()?, chars.next()) {
+            (c, None) if c.is_ascii_uppercase() => {
+                modifiers = modifiers | Modifiers::SHIFT;
+                letter(c.to_ascii_lowercase())?
+            }
+            (c, None) if c.is_ascii_lowercase() => letter(c)?,
+            (c, None) => CHARACTERS.iter().find(|(ch, _)| *ch == c)?.1,
+            _ => {
+                let key = key.to_ascii_lowercase();
+                NAMED.iter().find(|(name, _)| *name == key)?.1
+            }
+        };
+
+        Some(Chord::new(key_code, modifiers))
+    }
+
+    /// The digit this chord types, if it is a bare number key.
+    fn digit(self) -> Option<usize> {
+        if !self.modifiers.is_empty() {
+            return None;
+        }
+
+        DIGITS.iter().position(|k| *k == self.key_code)
+    }
+
+    /// Whether this is a modifier pressed on its own, which is never a key of a binding but
+    /// only comes before one.
+    fn is_modifier(self) -> bool {
+        matches!(
+            self.key_code,
+            KeyCode::LShift
+                | KeyCode::RShift
+                | KeyCode::LControl
+                | KeyCode::RControl
+                | KeyCode::LAlt
+                | KeyCode::RAlt
+                | KeyCode::LWin
+                | KeyCode::RWin
+        )
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = LETTERS.iter().position(|k| *k == self.key_code);
+        let shift = self.modifiers.contains(Modifiers::SHIFT);
+
+        for (modifier, prefix) in [
+            (Modifiers::CTRL, "C-"),
+            (Modifiers::ALT, "A-"),
+            (Modifiers::LOGO, "M-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(prefix)?;
+            }
+        }
+
+        match letter {
+            Some(i) if shift => write!(f, "{}", (b'A' + i as u8) as char),
+            Some(i) => write!(f, "{}", (b'a' + i as u8) as char),
+            None if shift && self.key_code == KeyCode::Semicolon => f.write_str(":"),
+            None => {
+                if shift {
+                    f.write_str("S-")?;
+                }
+
+                match CHARACTERS.iter().find(|(_, k)| *k == self.key_code) {
+                    Some((c, _)) => write!(f, "{c}"),
+                    None => match NAMED.iter().find(|(_, k)| *k == self.key_code) {
+                        Some((name, _)) => f.write_str(name),
+                        None => write!(f, "{:?}", self.key_code),
+                    },
+                }
+            }
+        }
+    }
+}
+
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+];
+
+const DIGITS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+const CHARACTERS: &[(char, KeyCode)] = &[
+    ('0', KeyCode::Key0),
+    ('1', KeyCode::Key1),
+    ('2', KeyCode::Key2),
+    ('3', KeyCode::Key3),
+    ('4', KeyCode::Key4),
+    ('5', KeyCode::Key5),
+    ('6', KeyCode::Key6),
+    ('7', KeyCode::Key7),
+    ('8', KeyCode::Key8),
+    ('9', KeyCode::Key9),
+    ('/', KeyCode::Slash),
+    ('\\', KeyCode::Backslash),
+    (':', KeyCode::Colon),
+    (';', KeyCode::Semicolon),
+    (',', KeyCode::Comma),
+    ('.', KeyCode::Period),
+    ('\'', KeyCode::Apostrophe),
+    ('`', KeyCode::Grave),
+    ('=', KeyCode::Equals),
+    ('[', KeyCode::LBracket),
+    (']', KeyCode::RBracket),
+];
+
+/// Keys spelled out by name. The first name of a key is the one it is displayed with.
+const NAMED: &[(&str, KeyCode)] = &[
+    ("esc", KeyCode::Escape),
+    ("escape", KeyCode::Escape),
+    ("enter", KeyCode::Enter),
+    ("return", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("space", KeyCode::Space),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("del", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("minus", KeyCode::Minus),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+];
+
+fn letter(c: char) -> Option<KeyCode> {
+    c.is_ascii_lowercase()
+        .then(|| LETTERS[(c as u8 - b'a') as usize])
+}
+
+/// The action a name in the keymap stands for.
+fn action(name: &str) -> Option<Action> {
+    Some(match name {
+        "none" => Action::None,
+        "up" => Action::Up,
+        "down" => Action::Down,
+        "top" => Action::Top,
+        "bottom" => Action::Bottom,
+        "up_dir" => Action::UpDir,
+        "open" => Action::Open,
+        "back" => Action::Back,
+        "new_tab" => Action::NewTab(None),
+        "close_tab" => Action::CloseTab,
+        "next_tab" => Action::NextTab,
+        "previous_tab" => Action::PreviousTab,
+        "move_tab_left" => Action::MoveTabLeft,
+        "move_tab_right" => Action::MoveTabRight,
+        "copy_to_tab" => Action::CopyToTab(None),
+        "move_to_tab" => Action::MoveToTab(None),
+        "forward" => Action::Forward,
+        "delete" => Action::Delete,
+        "confirm_delete" => Action::ConfirmDelete,
+        "cancel_delete" => Action::CancelDelete,
+        "toggle_current" => Action::ToggleCurrent,
+        "visual_mode" => Action::VisualMode,
+        "invert_selection" => Action::InvertSelection,
+        "select_all" => Action::SelectAll,
+        "yank" => Action::Yank,
+        "cut" => Action::Cut,
+        "paste" => Action::Paste,
+        "paste_overwrite" => Action::PasteWith(ConflictPolicy::Overwrite),
+        "paste_skip" => Action::PasteWith(ConflictPolicy::Skip),
+        "paste_rename" => Action::PasteWith(ConflictPolicy::Rename),
+        "rename" => Action::Rename,
+        "bulk_rename" => Action::BulkRename,
+        "sort_name" => Action::Sort(SortKey::Name),
+        "sort_size" => Action::Sort(SortKey::Size),
+        "sort_modified" => Action::Sort(SortKey::Modified),
+        "sort_extension" => Action::Sort(SortKey::Extension),
+        "sort_type" => Action::Sort(SortKey::Type),
+        "toggle_dirs_first" => Action::ToggleDirsFirst,
+        "toggle_reverse" => Action::ToggleReverse,
+        "remember_sort" => Action::RememberSort(true),
+        "forget_sort" => Action::RememberSort(false),
+        "toggle_hidden" => Action::ToggleHidden,
+        "toggle_ignore" => Action::ToggleIgnore,
+        "new_file" => Action::NewMode(Mode::Create(CreateKind::File)),
+        "new_directory" => Action::NewMode(Mode::Create(CreateKind::Directory)),
+        "undo" => Action::Undo,
+        "redo" => Action::Redo,
+        "cancel_job" => Action::CancelJob,
+        "dismiss_notifications" => Action::DismissNotifications,
+        "toggle_preview" => Action::TogglePreview,
+        "toggle_long_listing" => Action::ToggleLongListing,
+        "toggle_size_column" => Action::ToggleDetail(Detail::Size),
+        "toggle_modified_column" => Action::ToggleDetail(Detail::Modified),
+        "toggle_permissions_column" => Action::ToggleDetail(Detail::Permissions),
+        "toggle_owner_column" => Action::ToggleDetail(Detail::Owner),
+        "toggle_target_column" => Action::ToggleDetail(Detail::Target),
+        "normal_mode" => Action::NewMode(Mode::Normal),
+        "search" => Action::NewMode(Mode::Search(SearchMode::Regular)),
+        "global_search" => Action::NewMode(Mode::Search(SearchMode::Global(DEFAULT_GLOBAL_DEPTH))),
+        "deeper_search" => Action::DeeperSearch,
+        "shallower_search" => Action::ShallowerSearch,
+        "freeze_search" => Action::FreezeSearch,
+        "pop_from_search" => Action::PopFromSearch,
+        "command_mode" => Action::NewMode(Mode::Command),
+        "trash_view" => Action::NewView(View::Trash(TrashView::default())),
+        "bookmarks_view" => Action::NewView(View::Bookmarks(BookmarksView::default())),
+        "set_mark" => Action::NewMode(Mode::SetMark),
+        "go_to_mark" => Action::NewMode(Mode::GoToMark),
+        "jump" => Action::NewMode(Mode::Jump),
+        "settings_view" => Action::NewView(View::Settings(SettingsView::default())),
+        "main_view" => Action::NewView(View::MainView),
+        "restore_trashed" => Action::RestoreTrashed,
+        "empty_trash" => Action::EmptyTrash,
+        "commit" => Action::Commit,
+        "complete" => Action::Complete,
+        "history_older" => Action::HistoryOlder,
+        "history_newer" => Action::HistoryNewer,
+        "backspace" => Action::Edit(Edit::Backspace),
+        "delete_char" => Action::Edit(Edit::Delete),
+        "cursor_left" => Action::Edit(Edit::Left),
+        "cursor_right" => Action::Edit(Edit::Right),
+        "cursor_home" => Action::Edit(Edit::Home),
+        "cursor_end" => Action::Edit(Edit::End),
+        "quit" => Action::Quit,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KeymapFile {
+    preset: Option<String>,
+    /// Bindings of each section, in the order they were written. Needs toml's `preserve_order`.
+    #[serde(flatten)]
+    sections: HashMap<String, toml::value::Table>,
+}
+
+/// Keys typed so far that don't make up a whole binding yet, along with the count typed before
+/// them.
+#[derive(Debug, Default)]
+pub struct Pending {
+    chords: Vec<Chord>,
+    count: Option<usize>,
+    /// Bumped with every key, so a timeout can tell whether more keys came in since it started.
+    pub generation: usize,
+}
+
+impl Pending {
+    /// Whether a sequence is waiting to be finished or timed out.
+    pub fn is_waiting(&self) -> bool {
+        !self.chords.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.chords.clear();
+        self.count = None;
+    }
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(count) = self.count {
+            write!(f, "{count}")?;
+        }
+
+        for chord in &self.chords {
+            write!(f, "{chord}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An action to take, with the count typed before its keys.
+pub type Counted = (Action, Option<usize>);
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<(Section, Vec<Chord>), Action>,
+    /// Every proper prefix of a bound sequence.
+    prefixes: HashSet<(Section, Vec<Chord>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let (keymap, errors) = Keymap::from_config("");
+        debug_assert!(errors.is_empty(), "{errors:?}");
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Adds a key to `pending`, returning whatever actions it completes.
+    ///
+    /// A sequence that is bound but also starts a longer one waits for the next key, or for
+    /// [`Keymap::timeout`]. Number keys that aren't bound themselves make up a count in normal
+    /// and visual mode. Modifiers pressed on their own are ignored.
+    pub fn feed(&self, section: Section, pending: &mut Pending, chord: Chord) -> Vec<Counted> {
+        if chord.is_modifier() {
+            return vec![];
+        }
+
+        pending.generation += 1;
+
+        let counts = matches!(section, Section::Normal | Section::Visual);
+        if counts && pending.chords.is_empty() {
+            let bound = self.bindings.contains_key(&(section, vec![chord]))
+                || self.prefixes.contains(&(section, vec![chord]));
+
+            match chord.digit() {
+                // A leading 0 is left alone, it may still be bound to something
+                Some(digit) if !bound && (digit > 0 || pending.count.is_some()) => {
+                    let count = pending.count.unwrap_or(0);
+                    pending.count = Some(count.saturating_mul(10).saturating_add(digit));
+                    return vec![];
+                }
+                _ => (),
+            }
+        }
+
+        pending.chords.push(chord);
+        let key = (section, pending.chords.clone());
+
+        if self.prefixes.contains(&key) {
+            return vec![];
+        }
+
+        if let Some(action) = self.bindings.get(&key) {
+            let counted = (action.clone(), pending.count);
+            pending.clear();
+            return vec![counted];
+        }
+
+        // The new key doesn't fit, run what came before it if that was bound, then let the key
+        // start over on its own
+        let mut chords = std::mem::take(&mut pending.chords);
+        let _ = chords.pop();
+        if chords.is_empty() {
+            pending.clear();
+            return vec![];
+        }
+
+        let mut actions: Vec<_> = self
+            .bindings
+            .get(&(section, chords))
+            .map(|action| (action.clone(), pending.count))
+            .into_iter()
+            .collect();
+        pending.clear();
+
+        actions.extend(self.feed(section, pending, chord));
+        actions
+    }
+
+    /// The user stopped typing, runs the pending sequence if it is bound on its own.
+    pub fn timeout(&self, section: Section, pending: &mut Pending) -> Option<Counted> {
+        let key = (section, std::mem::take(&mut pending.chords));
+        let counted = self
+            .bindings
+            .get(&key)
+            .map(|action| (action.clone(), pending.count));
+        pending.clear();
+
+        counted
+    }
+
+    /// Keys bound to an action in `section` that `is_action` picks, the shortest if several are.
+    /// `None` if the user unbound it.
+    pub fn keys_for(
+        &self,
+        section: Section,
+        is_action: impl Fn(&Action) -> bool,
+    ) -> Option<String> {
+        self.bindings
+            .iter()
+            .filter(|((s, _), action)| *s == section && is_action(action))
+            .map(|((_, chords), _)| chords.iter().map(Chord::to_string).collect::<String>())
+            // Bindings aren't in any order, ties go alphabetically so hints don't change
+            .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+    }
+
+    /// Reads the user's keymap. Problems with it are returned rather than failing the whole
+    /// load, whatever could be understood is still applied.
+    pub async fn load() -> (Keymap, Vec<String>) {
+        let path = dirs::config_dir().join("keymap.toml");
+
+        match read_to_string(&path).await {
+            Ok(contents) => Keymap::from_config(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Keymap::default(), vec![]),
+            Err(e) => (Keymap::default(), vec![format!("{}: {e}", path.display())]),
+        }
+    }
+
+    fn from_config(contents: &str) -> (Keymap, Vec<String>) {
+        let mut errors = vec![];
+
+        let file: KeymapFile = match toml::from_str(contents) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(format!("keymap.toml: {e}"));
+                KeymapFile::default()
+            }
+        };
+
+        let preset = match file.preset.as_deref() {
+            None | Some("colemak") => COLEMAK,
+            Some("qwerty") => QWERTY,
+            Some(other) => {
+                errors.push(format!("keymap.toml: unknown preset {other:?}"));
+                COLEMAK
+            }
+        };
+
+        let mut keymap = Keymap {
+            bindings: HashMap::new(),
+            prefixes: HashSet::new(),
+        };
+
+        match toml::from_str::<KeymapFile>(preset) {
+            Ok(preset) => keymap.apply(preset.sections, &mut errors),
+            Err(e) => errors.push(format!("preset: {e}")),
+        }
+        keymap.apply(file.sections, &mut errors);
+
+        for (section, chords) in keymap.bindings.keys() {
+            for len in 1..chords.len() {
+                let _ = keymap.prefixes.insert((*section, chords[..len].to_vec()));
+            }
+        }
+
+        (keymap, errors)
+    }
+
+    /// Adds `sections` on top of the current bindings. Where one section binds the same keys
+    /// twice, the later binding wins.
+    fn apply(&mut self, sections: HashMap<String, toml::value::Table>, errors: &mut Vec<String>) {
+        for (section_name, bindings) in sections {
+            let section = match Section::from_name(&section_name) {
+                Some(section) => section,
+                None => {
+                    errors.push(format!("unknown section [{section_name}]"));
+                    continue;
+                }
+            };
+
+            // Different spellings can mean the same keys, e.g. `S-a` and `A`
+            let mut seen = HashMap::new();
+            for (chord_name, action_name) in bindings {
+                // Sequences are chords separated by spaces, like `g g`
+                let chords: Option<Vec<_>> =
+                    chord_name.split_whitespace().map(Chord::parse).collect();
+                let chord = match chords {
+                    Some(chords) if !chords.is_empty() => chords,
+                    _ => {
+                        errors.push(format!("[{section_name}] unknown key {chord_name:?}"));
+                        continue;
+                    }
+                };
+
+                let action = match action_name.as_str().and_then(action) {
+                    Some(action) => action,
+                    None => {
+                        errors.push(format!(
+                            "[{section_name}] unknown action {action_name} for {chord_name:?}"
+                        ));
+                        continue;
+                    }
+                };
+
+                if let Some(other) = seen.insert(chord.clone(), chord_name.clone()) {
+                    errors.push(format!(
+                        "[{section_name}] {other:?} is the same key as {chord_name:?} and is \
+                         shadowed by it"
+                    ));
+                }
+
+                match action {
+                    Action::None => {
+                        let _ = self.bindings.remove(&(section, chord));
+                    }
+                    action => {
+                        let _ = self.bindings.insert((section, chord), action);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qwerty() -> Keymap {
+        let (keymap, errors) = Keymap::from_config("preset = \"qwerty\"");
+        assert!(errors.is_empty(), "{errors:?}");
+        keymap
+    }
+
+    fn chord(chord: &str) -> Chord {
+        Chord::parse(chord).unwrap_or_else(|| panic!("bad chord {chord:?}"))
+    }
+
+    /// Feeds the space separated chords in `keys`, a shift or ctrl press coming before each
+    /// chord that holds it like a keyboard would send.
+    fn feed(keymap: &Keymap, pending: &mut Pending, keys: &str) -> Vec<Counted> {
+        let mut actions = vec![];
+        for key in keys.split(' ').map(chord) {
+            if key.modifiers.contains(Modifiers::SHIFT) {
+                actions.extend(keymap.feed(
+                    Section::Normal,
+                    pending,
+                    Chord::new(KeyCode::LShift, Modifiers::SHIFT),
+                ));
+            }
+            if key.modifiers.contains(Modifiers::CTRL) {
+                actions.extend(keymap.feed(
+                    Section::Normal,
+                    pending,
+                    Chord::new(KeyCode::LControl, Modifiers::CTRL),
+                ));
+            }
+
+            actions.extend(keymap.feed(Section::Normal, pending, key));
+        }
+
+        actions
+    }
+
+    #[test]
+    fn presets_load_cleanly() {
+        for config in ["preset = \"colemak\"", "preset = \"qwerty\""] {
+            let (_, errors) = Keymap::from_config(config);
+            assert!(errors.is_empty(), "{config}: {errors:?}");
+        }
+    }
+
+    #[test]
+    fn later_spelling_of_the_same_key_wins() {
+        let config = "[normal]\n\"S-a\" = \"down\"\nA = \"up\"\n";
+        for _ in 0..10 {
+            let (keymap, errors) = Keymap::from_config(config);
+            assert_eq!(errors.len(), 1, "{errors:?}");
+            assert!(errors[0].contains("\"S-a\" is the same key as \"A\""));
+
+            let actions = keymap.feed(Section::Normal, &mut Pending::default(), chord("A"));
+            assert!(matches!(actions[..], [(Action::Up, None)]));
+        }
+    }
+
+    #[test]
+    fn single_key() {
+        let keymap = qwerty();
+        let actions = feed(&keymap, &mut Pending::default(), "j");
+        assert!(matches!(actions[..], [(Action::Down, None)]));
+    }
+
+    #[test]
+    fn count_before_a_shifted_key() {
+        let keymap = qwerty();
+        let actions = feed(&keymap, &mut Pending::default(), "5 G");
+        assert!(matches!(actions[..], [(Action::Bottom, Some(5))]));
+    }
+
+    #[test]
+    fn multi_digit_count() {
+        let keymap = qwerty();
+        let actions = feed(&keymap, &mut Pending::default(), "1 0 j");
+        assert!(matches!(actions[..], [(Action::Down, Some(10))]));
+    }
+
+    #[test]
+    fn count_before_a_control_key() {
+        let keymap = qwerty();
+        let actions = feed(&keymap, &mut Pending::default(), "3 C-o");
+        assert!(matches!(actions[..], [(Action::Back, Some(3))]));
+    }
+
+    #[test]
+    fn sequence_ending_in_a_shifted_key() {
+        let keymap = qwerty();
+        let mut pending = Pending::default();
+
+        assert!(feed(&keymap, &mut pending, "g").is_empty());
+        assert!(pending.is_waiting());
+
+        let actions = feed(&keymap, &mut pending, "T");
+        assert!(matches!(actions[..], [(Action::PreviousTab, None)]));
+        assert!(!pending.is_waiting());
+
+        let actions = feed(&keymap, &mut pending, ", K");
+        assert!(matches!(actions[..], [(Action::RememberSort(false), None)]));
+    }
+
+    #[test]
+    fn count_before_a_sequence() {
+        let keymap = qwerty();
+        let actions = feed(&keymap, &mut Pending::default(), "2 g t");
+        assert!(matches!(actions[..], [(Action::NextTab, Some(2))]));
+    }
+
+    #[test]
+    fn prefix_runs_on_timeout() {
+        let keymap = qwerty();
+        let mut pending = Pending::default();
+
+        assert!(feed(&keymap, &mut pending, "3 d").is_empty());
+        let counted = keymap.timeout(Section::Normal, &mut pending);
+        assert!(matches!(counted, Some((Action::Delete, Some(3)))));
+        assert!(!pending.is_waiting());
+        assert_eq!(pending.to_string(), "");
+    }
+
+    #[test]
+    fn unbound_prefix_times_out_to_nothing() {
+        let keymap = qwerty();
+        let mut pending = Pending::default();
+
+        assert!(feed(&keymap, &mut pending, "g").is_empty());
+        assert!(keymap.timeout(Section::Normal, &mut pending).is_none());
+        assert!(!pending.is_waiting());
+    }
+
+    #[test]
+    fn prefix_followed_by_another_key_runs_both() {
+        let keymap = qwerty();
+        let actions = feed(&keymap, &mut Pending::default(), "d j");
+        assert!(matches!(
+            actions[..],
+            [(Action::Delete, None), (Action::Down, None)]
+        ));
+    }
+
+    #[test]
+    fn unbound_key_clears_the_count() {
+        let keymap = qwerty();
+        let mut pending = Pending::default();
+
+        assert!(feed(&keymap, &mut pending, "4 F12").is_empty());
+        let actions = feed(&keymap, &mut pending, "j");
+        assert!(matches!(actions[..], [(Action::Down, None)]));
+    }
+
+    #[test]
+    fn pending_shows_count_and_keys() {
+        let keymap = qwerty();
+        let mut pending = Pending::default();
+
+        assert!(feed(&keymap, &mut pending, "1 2 g").is_empty());
+        assert_eq!(pending.to_string(), "12g");
+    }
+
+    #[test]
+    fn modifiers_alone_do_nothing() {
+        let keymap = qwerty();
+        let mut pending = Pending::default();
+
+        let shift = Chord::new(KeyCode::LShift, Modifiers::SHIFT);
+        assert!(keymap.feed(Section::Normal, &mut pending, shift).is_empty());
+        assert_eq!(pending.generation, 0);
+    }
+
+    #[test]
+    fn keys_for_picks_the_shortest_binding() {
+        let keymap = qwerty();
+        let cancel = keymap.keys_for(Section::ConfirmDelete, |a| {
+            matches!(a, Action::CancelDelete)
+        });
+        assert_eq!(cancel.as_deref(), Some("n"));
+
+        let config = "preset = \"qwerty\"\n[confirm_delete]\ny = \"none\"\n";
+        let (keymap, errors) = Keymap::from_config(config);
+        assert!(errors.is_empty(), "{errors:?}");
+        let confirm = keymap.keys_for(Section::ConfirmDelete, |a| {
+            matches!(a, Action::ConfirmDelete)
+        });
+        assert_eq!(confirm, None);
+    }
+}
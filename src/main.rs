@@ -8,10 +8,27 @@
 )]
 
 mod app;
+mod bulk_rename;
+mod command;
+mod details;
+mod dirs;
+mod frecency;
+mod highlight;
+mod input;
+mod jobs;
+mod journal;
+mod jumps;
+mod keymap;
+mod marks;
 mod mode;
+mod notification;
+mod preview;
+mod sort;
 mod tasks;
 mod theme;
+mod trash;
 mod ui;
+mod watch;
 
 use std::io;
 
@@ -0,0 +1,119 @@
+//! Directories saved under a letter to jump back to, like vim's marks. They are kept in the
+//! data directory, so they survive a restart.
+
+use std::path::PathBuf;
+
+use crate::{
+    app::{LoadError, SaveError},
+    dirs,
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mark {
+    pub letter: char,
+    /// Shown in the bookmarks view, the name of the directory unless it was renamed.
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+/// Every mark, ordered by letter.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Marks {
+    #[serde(default, rename = "mark")]
+    marks: Vec<Mark>,
+    /// `marks.toml` couldn't be read, new marks aren't saved over it.
+    #[serde(skip)]
+    unreadable: bool,
+}
+
+impl Marks {
+    fn path() -> PathBuf {
+        dirs::data_dir().join("marks.toml")
+    }
+
+    /// Reads the saved marks. When `marks.toml` can't be read, there are no marks and the
+    /// file is kept as it is instead of being saved over.
+    pub async fn load() -> (Marks, Option<LoadError>) {
+        let path = Self::path();
+        match dirs::load_toml(&path).await {
+            Ok(marks) => (marks, None),
+            Err(e) => {
+                let marks = Marks {
+                    unreadable: true,
+                    ..Marks::default()
+                };
+                let error = LoadError {
+                    path,
+                    reason: e.to_string(),
+                };
+                (marks, Some(error))
+            }
+        }
+    }
+
+    pub fn get(&self, letter: char) -> Option<&Mark> {
+        self.marks.iter().find(|m| m.letter == letter)
+    }
+
+    /// Saves `dir` under `letter`, replacing whatever was saved there before.
+    pub fn set(&mut self, letter: char, dir: PathBuf) {
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| dir.to_string_lossy().into_owned());
+
+        self.remove(letter);
+        self.marks.push(Mark { letter, name, dir });
+        self.marks.sort_by_key(|m| m.letter);
+    }
+
+    pub fn rename(&mut self, letter: char, name: String) {
+        if let Some(mark) = self.marks.iter_mut().find(|m| m.letter == letter) {
+            mark.name = name;
+        }
+    }
+
+    pub fn remove(&mut self, letter: char) {
+        self.marks.retain(|m| m.letter != letter);
+    }
+
+    /// Marks whose name or directory match `query`, best first. Without a query that is all of
+    /// them, by letter.
+    pub fn matching(&self, query: &str) -> Vec<&Mark> {
+        if query.is_empty() {
+            return self.marks.iter().collect();
+        }
+
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<_> = self
+            .marks
+            .iter()
+            .filter_map(|m| {
+                let haystack = format!("{} {}", m.name, m.dir.display());
+                matcher
+                    .fuzzy_match(&haystack, query)
+                    .map(|score| (score, m))
+            })
+            .collect();
+
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, m)| m).collect()
+    }
+
+    /// Writes the marks to disk.
+    pub fn save(&self) -> impl std::future::Future<Output = Result<(), SaveError>> {
+        let path = Self::path();
+        let save = (!self.unreadable).then(|| dirs::save_toml(path.clone(), self));
+        async move {
+            match save {
+                Some(save) => save.await.map_err(|e| SaveError {
+                    path,
+                    reason: e.to_string(),
+                }),
+                None => Ok(()),
+            }
+        }
+    }
+}
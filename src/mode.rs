@@ -1,88 +1,89 @@
 use std::fmt::Debug;
 
-use iced::keyboard::Modifiers;
-use iced_native::keyboard::{Event, KeyCode};
+use iced_native::keyboard::Event;
 
-use crate::app::{Action, SettingsView, View};
+use crate::{
+    app::Action,
+    input::Edit,
+    keymap::{Chord, Counted, Keymap, Pending, Section},
+    tasks::CreateKind,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Normal,
     Search(SearchMode),
+    /// Waiting for y/n before deleting directories.
+    ConfirmDelete {
+        items: usize,
+        bytes: u64,
+    },
+    /// Waiting for the user to pick how to paste over existing files.
+    ResolveConflict {
+        conflicts: usize,
+    },
+    /// Editing the name of the hovered file in place.
+    Rename,
+    /// Typing the name of a new file or directory.
+    Create(CreateKind),
+    /// Typing a `:` command.
+    Command,
+    /// Selecting every file between `anchor` and the hovered one.
+    Visual {
+        anchor: usize,
+    },
+    /// Waiting for the letter to save the current directory under.
+    SetMark,
+    /// Waiting for the letter of the mark to jump to.
+    GoToMark,
+    /// Typing part of the path of a visited directory to jump to.
+    Jump,
 }
 
 impl Mode {
-    pub fn parse_event(self, key: Event) -> Action {
-        match self {
-            Mode::Normal => Mode::parse_normal(key),
-            Mode::Search(_) => Mode::parse_search(key),
-        }
-    }
-
-    pub fn parse_normal(key: Event) -> Action {
-        // let Event { code } = key;
-        if let Event::KeyPressed {
-            key_code,
-            modifiers,
-        } = key
-        {
-            match key_code {
-                KeyCode::Escape => Action::NewMode(Mode::Normal),
-                KeyCode::N => Action::UpDir,
-                KeyCode::E => Action::Down,
-                KeyCode::I => Action::Up,
-                KeyCode::O => Action::Open,
-                KeyCode::D => Action::Delete,
-                KeyCode::T => Action::ToggleCurrent,
-                KeyCode::S if modifiers.contains(Modifiers::CTRL) => {
-                    Action::NewView(View::Settings(SettingsView::default()))
+    pub fn parse_event(self, key: Event, keymap: &Keymap, pending: &mut Pending) -> Vec<Counted> {
+        let action = match key {
+            Event::KeyPressed {
+                key_code,
+                modifiers,
+            } => return keymap.feed(self.section(), pending, Chord::new(key_code, modifiers)),
+            // Typing goes to whatever is being edited
+            Event::CharacterReceived(c) => match self {
+                Mode::Search(_) if c.is_alphabetic() => Action::AddToSearch(c),
+                Mode::Rename | Mode::Create(_) | Mode::Command | Mode::Jump if !c.is_control() => {
+                    Action::Edit(Edit::Insert(c))
                 }
-                KeyCode::S | KeyCode::Slash => Action::NewMode(Mode::Search(SearchMode::Regular)),
-                KeyCode::Q => Action::Quit,
-                _ => Action::None,
-            }
-        } else {
-            Action::None
-        }
-    }
-
-    fn parse_search(key: Event) -> Action {
-        // let KeyEvent { code, modifiers } = key;
+                Mode::SetMark if !c.is_control() => Action::SetMark(c),
+                Mode::GoToMark if !c.is_control() => Action::GoToMark(c),
+                _ => return vec![],
+            },
+            _ => return vec![],
+        };
 
-        //TOOD move around in search with shift
-        // if modifiers.contains(KeyModifiers::SHIFT) {
-        //     match code {
-        //         Event::CharacterReceived(c) => {
-        //             return Mode::parse_normal(KeyEvent::new(
-        //                 Event::CharacterReceived(c.to_ascii_lowercase()),
-        //                 KeyModifiers::NONE,
-        //             ));
-        //         }
-        //         _ => (),
-        //     }
-        // }
+        vec![(action, None)]
+    }
 
-        // let enter = pressed(KeyCode::Enter);
-        if let Event::KeyPressed { key_code, .. } = key {
-            match key_code {
-                KeyCode::Enter => Action::FreezeSearch,
-                KeyCode::Backspace => Action::PopFromSearch,
-                KeyCode::Escape => Action::NewMode(Mode::Normal),
-                _ => Action::None,
-            }
-        } else {
-            match key {
-                Event::CharacterReceived(c) if c.is_alphabetic() => Action::AddToSearch(c),
-                _ => Action::None,
-            }
+    /// Keymap section this mode takes its bindings from.
+    pub fn section(self) -> Section {
+        match self {
+            Mode::Normal => Section::Normal,
+            Mode::Search(_) => Section::Search,
+            Mode::ConfirmDelete { .. } => Section::ConfirmDelete,
+            Mode::ResolveConflict { .. } => Section::ResolveConflict,
+            Mode::Rename | Mode::Create(_) | Mode::Command | Mode::Jump => Section::Prompt,
+            Mode::Visual { .. } => Section::Visual,
+            Mode::SetMark | Mode::GoToMark => Section::Mark,
         }
     }
 }
 
+/// How many levels of subdirectories a global search walks by default.
+pub const DEFAULT_GLOBAL_DEPTH: usize = 3;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SearchMode {
     Regular,
     // Files in subdirectories as well
     // .0 is depth
-    // Global(usize),
+    Global(usize),
 }
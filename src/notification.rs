@@ -0,0 +1,89 @@
+//! Messages for the user, shown as toasts that go away on their own after a while.
+
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// How long a notification stays up unless it is dismissed.
+    fn lifetime(self) -> Duration {
+        match self {
+            Severity::Info => Duration::from_secs(4),
+            Severity::Warning => Duration::from_secs(8),
+            Severity::Error => Duration::from_secs(15),
+        }
+    }
+}
+
+pub type NotificationId = usize;
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub id: NotificationId,
+    pub severity: Severity,
+    pub message: String,
+    expires: Instant,
+}
+
+/// Notifications currently up, oldest first.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    queue: Vec<Notification>,
+    next_id: NotificationId,
+}
+
+impl Notifications {
+    pub fn push(&mut self, severity: Severity, message: String) {
+        match severity {
+            Severity::Info => info!("{message}"),
+            Severity::Warning => warn!("{message}"),
+            Severity::Error => error!("{message}"),
+        }
+
+        let expires = Instant::now() + severity.lifetime();
+
+        // Something failing over and over shows up once, for as long as it keeps failing
+        if let Some(last) = self.queue.last_mut() {
+            if last.severity == severity && last.message == message {
+                last.expires = expires;
+                return;
+            }
+        }
+
+        self.queue.push(Notification {
+            id: self.next_id,
+            severity,
+            message,
+            expires,
+        });
+        self.next_id += 1;
+    }
+
+    pub fn dismiss(&mut self, id: NotificationId) {
+        self.queue.retain(|n| n.id != id);
+    }
+
+    pub fn dismiss_all(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Drops the notifications that have been up long enough by `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.queue.retain(|n| n.expires > now);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.queue.iter()
+    }
+}
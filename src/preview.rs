@@ -0,0 +1,195 @@
+//! Contents of the hovered file, shown next to the file list.
+
+use std::{
+    any::TypeId,
+    fmt::Write,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::{futures::future::pending, image, Subscription};
+use iced_native::subscription::unfold;
+use tokio::{fs::File as FsFile, io::AsyncReadExt, task::spawn_blocking, time::sleep};
+
+use crate::{
+    app::Message,
+    highlight::{highlight, Line},
+    sort::Sort,
+    tasks::{get_files, Filter},
+};
+
+/// How many lines of a text file, or entries of a directory, are shown.
+const MAX_LINES: usize = 100;
+/// How much of a file is read to preview it, and to tell text from binary.
+const READ_LIMIT: usize = 64 * 1024;
+/// How much of a binary file is dumped.
+const HEXDUMP_LIMIT: usize = 512;
+/// Largest image that is loaded for a thumbnail.
+const IMAGE_LIMIT: u64 = 32 * 1024 * 1024;
+/// Files hovered for less than this aren't loaded at all, so scrolling past them stays fast.
+const DELAY: Duration = Duration::from_millis(50);
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp", "ico", "tiff"];
+
+#[derive(Debug, Clone)]
+pub enum Preview {
+    /// Highlighted lines from the start of a text file.
+    Text(Vec<Line>),
+    /// Entries of a directory, listed like the file list would.
+    Directory(Vec<String>),
+    Image(image::Handle),
+    Hexdump(String),
+    Error(String),
+}
+
+/// Loads the preview of `path`, sending `PreviewLoaded` once it is done. Dropping the
+/// subscription, e.g. when another file is hovered, cancels the load.
+pub fn preview(path: PathBuf, filter: Filter, sort: Sort) -> Subscription<Message> {
+    struct PreviewLoader;
+
+    unfold(
+        (TypeId::of::<PreviewLoader>(), path.clone(), filter, sort),
+        Some(path),
+        move |path| async move {
+            match path {
+                Some(path) => {
+                    sleep(DELAY).await;
+                    let preview = load(&path, filter, sort)
+                        .await
+                        .unwrap_or_else(|e| Preview::Error(e.to_string()));
+
+                    (Some(Message::PreviewLoaded(path, preview)), None)
+                }
+                None => pending().await,
+            }
+        },
+    )
+}
+
+async fn load(path: &Path, filter: Filter, sort: Sort) -> io::Result<Preview> {
+    let metadata = tokio::fs::metadata(path).await?;
+
+    if metadata.is_dir() {
+        let (mut files, errors) = get_files(path.to_path_buf(), 0, filter).await;
+        if let (true, Some(e)) = (files.is_empty(), errors.first()) {
+            return Ok(Preview::Error(e.reason.clone()));
+        }
+
+        files.sort_by(|a, b| sort.compare(a, b));
+        let names = files
+            .iter()
+            .take(MAX_LINES)
+            .map(|f| {
+                let after = if f.metadata.is_dir() { "/" } else { "" };
+                format!("{}{after}", f.display_name())
+            })
+            .collect();
+
+        return Ok(Preview::Directory(names));
+    }
+
+    let is_image = matches!(
+        path.extension(),
+        Some(ext) if IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+    );
+    if is_image && metadata.len() <= IMAGE_LIMIT {
+        let bytes = tokio::fs::read(path).await?;
+        return Ok(Preview::Image(image::Handle::from_memory(bytes)));
+    }
+
+    let bytes = read_start(path).await?;
+    let text = match text(&bytes) {
+        Some(text) => text.to_string(),
+        None => {
+            let dump = hexdump(&bytes[..bytes.len().min(HEXDUMP_LIMIT)]);
+            return Ok(Preview::Hexdump(dump));
+        }
+    };
+
+    let lines: Vec<_> = text.split_inclusive('\n').take(MAX_LINES).collect();
+    let text = lines.concat();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // Highlighting is plenty of work for a big file, keep it off the async threads
+    Ok(
+        match spawn_blocking(move || highlight(&name, &text)).await {
+            Ok(lines) => Preview::Text(lines),
+            Err(e) => Preview::Error(e.to_string()),
+        },
+    )
+}
+
+/// Reads only as much of the start of a file as can be shown: `MAX_LINES` lines, or
+/// `READ_LIMIT` bytes for files with long lines or no lines at all.
+async fn read_start(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = FsFile::open(path).await?;
+    let mut bytes = vec![];
+    let mut chunk = [0; 8 * 1024];
+
+    while bytes.len() < READ_LIMIT {
+        let read = file.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+
+        bytes.extend_from_slice(&chunk[..read]);
+        if bytes.iter().filter(|&&b| b == b'\n').count() >= MAX_LINES {
+            break;
+        }
+    }
+
+    bytes.truncate(READ_LIMIT);
+    Ok(bytes)
+}
+
+/// The bytes as text, unless they look binary. Reading stops at an arbitrary byte, so a
+/// character cut off at the end is fine.
+fn text(bytes: &[u8]) -> Option<&str> {
+    if bytes.contains(&0) {
+        return None;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+/// Like `hexdump -C`: offset, 16 bytes in hex and the printable ones as text.
+fn hexdump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x} ", i * 16);
+
+        for j in 0..16 {
+            if j % 8 == 0 {
+                dump.push(' ');
+            }
+
+            match chunk.get(j) {
+                Some(byte) => {
+                    let _ = write!(dump, "{byte:02x} ");
+                }
+                None => dump.push_str("   "),
+            }
+        }
+
+        dump.push_str(" |");
+        dump.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        dump.push_str("|\n");
+    }
+
+    dump
+}
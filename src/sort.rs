@@ -0,0 +1,322 @@
+//! Ordering of the file list, and the directories that keep their own.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    app::{File, LoadError, SaveError},
+    dirs,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Natural order, so `file2` comes before `file10`.
+    Name,
+    /// Largest first.
+    Size,
+    /// Most recently modified first.
+    Modified,
+    Extension,
+    /// Directories, then files, then symlinks and anything else.
+    Type,
+}
+
+impl SortKey {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "name" => SortKey::Name,
+            "size" => SortKey::Size,
+            "modified" | "time" => SortKey::Modified,
+            "extension" | "ext" => SortKey::Extension,
+            "type" => SortKey::Type,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Sort {
+    pub key: SortKey,
+    pub dirs_first: bool,
+    pub reverse: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            dirs_first: true,
+            reverse: false,
+        }
+    }
+}
+
+impl Sort {
+    pub fn compare(&self, a: &File, b: &File) -> Ordering {
+        if self.dirs_first {
+            match (a.metadata.is_dir(), b.metadata.is_dir()) {
+                (true, false) => return Ordering::Less,
+                (false, true) => return Ordering::Greater,
+                _ => (),
+            }
+        }
+
+        let by_name = || natural_cmp(&a.display_name(), &b.display_name());
+        let ordering = match self.key {
+            SortKey::Name => by_name(),
+            SortKey::Size => b.metadata.len().cmp(&a.metadata.len()).then_with(by_name),
+            SortKey::Modified => {
+                let modified = |f: &File| f.metadata.modified().ok();
+                modified(b).cmp(&modified(a)).then_with(by_name)
+            }
+            SortKey::Extension => {
+                let extension = |f: &File| {
+                    Path::new(&f.name)
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                };
+                extension(a).cmp(&extension(b)).then_with(by_name)
+            }
+            SortKey::Type => type_rank(a).cmp(&type_rank(b)).then_with(by_name),
+        };
+
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Shown in the status bar, e.g. `name, dirs first`.
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = match self.key {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Extension => "extension",
+            SortKey::Type => "type",
+        };
+        f.write_str(key)?;
+
+        if self.reverse {
+            f.write_str(" reversed")?;
+        }
+        if self.dirs_first {
+            f.write_str(", dirs first")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn type_rank(file: &File) -> u8 {
+    let file_type = file.metadata.file_type();
+    if file_type.is_dir() {
+        0
+    } else if file_type.is_file() {
+        1
+    } else if file_type.is_symlink() {
+        2
+    } else {
+        3
+    }
+}
+
+/// Compares names case insensitively, with runs of digits compared by their value.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a_chars);
+                let y = take_number(&mut b_chars);
+
+                // Without leading zeros the longer number is the larger one
+                let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                let _ = a_chars.next();
+                let _ = b_chars.next();
+            }
+        }
+    }
+
+    // Equal apart from case or leading zeros, still needs some order to be stable
+    a.cmp(b)
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
+    }
+
+    number
+}
+
+/// The sort used everywhere, and the directories that were told to remember their own.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sorts {
+    #[serde(default)]
+    default: Sort,
+    #[serde(default)]
+    dirs: HashMap<PathBuf, Sort>,
+    /// `sorts.toml` couldn't be read, so remembering sorts doesn't write to it.
+    #[serde(skip)]
+    unreadable: bool,
+}
+
+impl Sorts {
+    fn path() -> PathBuf {
+        dirs::data_dir().join("sorts.toml")
+    }
+
+    /// Reads the remembered sorts, or why they couldn't be. A broken `sorts.toml` is left for
+    /// the user to fix rather than replaced with the defaults.
+    pub async fn load() -> (Sorts, Option<LoadError>) {
+        let path = Self::path();
+        match dirs::load_toml(&path).await {
+            Ok(sorts) => (sorts, None),
+            Err(e) => {
+                let sorts = Sorts {
+                    unreadable: true,
+                    ..Sorts::default()
+                };
+                let error = LoadError {
+                    path,
+                    reason: e.to_string(),
+                };
+                (sorts, Some(error))
+            }
+        }
+    }
+
+    pub fn get(&self, dir: &Path) -> Sort {
+        self.dirs.get(dir).copied().unwrap_or(self.default)
+    }
+
+    pub fn is_remembered(&self, dir: &Path) -> bool {
+        self.dirs.contains_key(dir)
+    }
+
+    /// Changes the sort of `dir` if it remembers its own, the default one otherwise.
+    pub fn set(&mut self, dir: &Path, sort: Sort) {
+        match self.dirs.get_mut(dir) {
+            Some(dir_sort) => *dir_sort = sort,
+            None => self.default = sort,
+        }
+    }
+
+    /// Makes `dir` keep its current sort, or go back to the default one.
+    pub fn remember(&mut self, dir: &Path, remember: bool) {
+        if remember {
+            let sort = self.get(dir);
+            let _ = self.dirs.insert(dir.to_path_buf(), sort);
+        } else {
+            let _ = self.dirs.remove(dir);
+        }
+    }
+
+    /// Writes the sorts to disk, so they survive a restart.
+    pub fn save(&self) -> impl std::future::Future<Output = Result<(), SaveError>> {
+        let path = Self::path();
+        let save = (!self.unreadable).then(|| dirs::save_toml(path.clone(), self));
+        async move {
+            match save {
+                Some(save) => save.await.map_err(|e| SaveError {
+                    path,
+                    reason: e.to_string(),
+                }),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<_> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(
+            sorted(&["file10", "file2", "file1", "file100"]),
+            ["file1", "file2", "file10", "file100"]
+        );
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(sorted(&["b", "A", "a", "B"]), ["A", "a", "B", "b"]);
+        assert_eq!(natural_cmp("Notes", "notes2"), Ordering::Less);
+    }
+
+    #[test]
+    fn leading_zeros_dont_change_the_value() {
+        assert_eq!(natural_cmp("a007", "a7b"), Ordering::Less);
+        assert_eq!(natural_cmp("a010", "a9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn equal_names_are_still_ordered() {
+        assert_ne!(natural_cmp("a01", "a1"), Ordering::Equal);
+        assert_ne!(natural_cmp("A", "a"), Ordering::Equal);
+        assert_eq!(natural_cmp("a1", "a1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn prefixes_come_first() {
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("file1", "file1a"), Ordering::Less);
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+    }
+
+    #[test]
+    fn numbers_longer_than_any_integer() {
+        assert_eq!(
+            natural_cmp("x99999999999999999999999", "x100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn digits_and_letters_mix() {
+        assert_eq!(natural_cmp("1a", "a"), Ordering::Less);
+        assert_eq!(
+            sorted(&["v1.10.0", "v1.9.2", "v1.9.10"]),
+            ["v1.9.2", "v1.9.10", "v1.10.0"]
+        );
+    }
+}
@@ -1,49 +1,595 @@
-use std::path::PathBuf;
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
 
-// use tokio::sync::mpsc::Sender;
-use tokio::fs::read_dir;
+use ignore::{gitignore::Gitignore, Match, WalkBuilder};
+use log::warn;
+use tokio::{
+    fs::{
+        create_dir, create_dir_all, read_dir, remove_dir_all, remove_file, rename, set_permissions,
+        symlink_metadata, try_exists, File as FsFile, OpenOptions,
+    },
+    io::{AsyncReadExt, AsyncWriteExt},
+    task::spawn_blocking,
+};
 
-use crate::app::File;
+use crate::{
+    app::{CreateError, File, FileDeleteError, ListError, PasteError, RegisterKind, RenameError},
+    jobs::JobContext,
+    journal::Operation,
+    trash,
+};
 
-pub async fn get_files(dir: PathBuf) -> Vec<File> {
-    // let walk = WalkDir::new(path)
-    //     .skip_hidden(false)
-    //     .min_depth(1)
-    //     .max_depth(1);
+/// Which entries a listing leaves out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Filter {
+    /// Show files starting with a `.`.
+    pub show_hidden: bool,
+    /// Leave out whatever `.gitignore` and `.ignore` files exclude.
+    pub respect_ignore: bool,
+}
+
+/// Those of `dirs` that aren't directories anymore.
+pub async fn missing_dirs(dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut missing = vec![];
+    for dir in dirs {
+        let is_dir = tokio::fs::metadata(&dir).await.is_ok_and(|m| m.is_dir());
+        if !is_dir {
+            missing.push(dir);
+        }
+    }
+
+    missing
+}
+
+/// Lists the contents of `dir`. With a `max_depth` above 0 subdirectories are walked as well,
+/// down to `max_depth` levels below `dir`.
+///
+/// Whatever can't be read is returned as an error next to everything that could, an unreadable
+/// `dir` lists nothing.
+pub async fn get_files(
+    dir: PathBuf,
+    max_depth: usize,
+    filter: Filter,
+) -> (Vec<File>, Vec<ListError>) {
+    // The walker is blocking, and also what knows how to read ignore files
+    let walk = spawn_blocking({
+        let dir = dir.clone();
+        move || walk(&dir, max_depth, filter)
+    });
+
+    match walk.await {
+        Ok(listing) => listing,
+        Err(e) => (
+            vec![],
+            vec![ListError {
+                path: dir,
+                reason: e.to_string(),
+            }],
+        ),
+    }
+}
 
-    // let mut dir = fs::read_dir("aa").await.unwrap();
+fn walk(dir: &Path, max_depth: usize, filter: Filter) -> (Vec<File>, Vec<ListError>) {
+    let walker = WalkBuilder::new(dir)
+        .max_depth(Some(max_depth + 1))
+        .standard_filters(filter.respect_ignore)
+        .hidden(!filter.show_hidden)
+        .build();
 
     let mut files = vec![];
+    let mut errors = vec![];
+    for entry in walker {
+        // Unreadable subdirectories shouldn't stop the rest of the walk
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("skipping: {e}");
+                errors.push(list_error(e, dir));
+                continue;
+            }
+        };
+
+        // The walk starts with `dir` itself
+        let depth = match entry.depth().checked_sub(1) {
+            Some(depth) => depth,
+            None => continue,
+        };
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!("skipping {:?}: {e}", entry.path());
+                errors.push(list_error(e, entry.path()));
+                continue;
+            }
+        };
+
+        let path = entry.path().to_path_buf();
+        let parent = path.parent().unwrap_or(dir).to_path_buf();
+        let mut file = File::new(
+            entry.file_name().to_os_string(),
+            depth,
+            path,
+            parent,
+            metadata,
+        );
+
+        if entry.path_is_symlink() {
+            file.link_target = std::fs::read_link(entry.path()).ok();
+        }
+
+        files.push(file);
+    }
+
+    (files, errors)
+}
+
+/// Pulls the path out of a walker error, `fallback` is used for errors that don't have one.
+fn list_error(e: ignore::Error, fallback: &Path) -> ListError {
+    match e {
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            list_error(*err, fallback)
+        }
+        ignore::Error::WithPath { path, err } => ListError {
+            path,
+            reason: err.to_string(),
+        },
+        e => ListError {
+            path: fallback.to_path_buf(),
+            reason: e.to_string(),
+        },
+    }
+}
+
+/// What a listing with a [`Filter`] leaves out of a single directory, for files that show up
+/// after the listing was made. The ignore files are read once, up front.
+#[derive(Debug)]
+pub struct DirFilter {
+    filter: Filter,
+    /// Ignore files that apply to entries of the directory, closest first.
+    ignores: Vec<Gitignore>,
+}
+
+impl DirFilter {
+    /// Reads the ignore files that apply to entries of `dir` in the background.
+    pub async fn read(dir: PathBuf, filter: Filter) -> Self {
+        if !filter.respect_ignore {
+            return DirFilter {
+                filter,
+                ignores: vec![],
+            };
+        }
+
+        let ignores = spawn_blocking(move || read_ignores(&dir)).await;
+        DirFilter {
+            filter,
+            ignores: ignores.unwrap_or_default(),
+        }
+    }
+
+    /// Whether the listing would leave out `path`, which has to be directly in the directory.
+    pub fn is_filtered(&self, path: &Path, is_dir: bool) -> bool {
+        let hidden =
+            matches!(path.file_name(), Some(name) if name.to_string_lossy().starts_with('.'));
+        if hidden && !self.filter.show_hidden {
+            return true;
+        }
+
+        // The closest ignore file that says anything about the path wins
+        for ignore in &self.ignores {
+            match ignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => (),
+            }
+        }
+
+        false
+    }
+}
+
+/// Like the walker, `.gitignore` files only count inside a repository, and `.ignore` wins over
+/// `.gitignore` in the same directory.
+fn read_ignores(dir: &Path) -> Vec<Gitignore> {
+    let ancestors: Vec<_> = dir.ancestors().collect();
+    let repo_root = ancestors.iter().position(|dir| dir.join(".git").exists());
 
-    let mut dir = read_dir(dir).await.unwrap();
+    let mut ignores = vec![];
+    for (i, dir) in ancestors.iter().enumerate() {
+        let in_repo = matches!(repo_root, Some(root) if i <= root);
+        let names: &[&str] = if in_repo {
+            &[".ignore", ".gitignore"]
+        } else {
+            &[".ignore"]
+        };
+
+        for name in names {
+            let path = dir.join(name);
+            if path.is_file() {
+                ignores.push(Gitignore::new(path).0);
+            }
+        }
+    }
+
+    ignores
+}
+
+/// Moves every path into the trash, carrying on past the ones that fail.
+pub async fn trash_files(
+    paths: Vec<PathBuf>,
+    ctx: &mut JobContext,
+) -> (Vec<Operation>, Vec<FileDeleteError>) {
+    let mut ops = vec![];
+    let mut errors = vec![];
+
+    for path in paths {
+        let result = match ctx.check() {
+            Ok(()) => trash::trash(path.clone(), ctx).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(entry) => ops.push(Operation::Trashed(entry)),
+            Err(e) => errors.push(FileDeleteError {
+                path,
+                reason: e.to_string(),
+            }),
+        }
+
+        ctx.item_done();
+    }
+
+    (ops, errors)
+}
+
+/// What to do when a pasted file has the same name as one already in the target directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Trash the existing file and put the pasted one in its place.
+    Overwrite,
+    Skip,
+    /// Paste under a free name, `name (1).ext`.
+    Rename,
+}
+
+/// Renames `from` to `to`, refusing to replace anything already at `to`.
+pub async fn rename_file(from: PathBuf, to: PathBuf) -> Result<Operation, RenameError> {
+    let error = |reason: String| RenameError {
+        path: from.clone(),
+        reason,
+    };
+
+    match try_exists(&to).await {
+        Ok(false) => (),
+        Ok(true) => return Err(error(format!("{} already exists", to.display()))),
+        Err(e) => return Err(error(e.to_string())),
+    }
+
+    rename(&from, &to).await.map_err(|e| error(e.to_string()))?;
+
+    Ok(Operation::Renamed { from, to })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateKind {
+    File,
+    Directory,
+}
+
+/// Creates `name` inside `dir`, along with any missing directories leading up to it.
+///
+/// Returns the entry directly inside `dir` that leads to the new file, e.g. `a` for `a/b/c.txt`,
+/// and the operation to journal, which covers the topmost directory that didn't exist before.
+pub async fn create_entry(
+    dir: PathBuf,
+    name: String,
+    kind: CreateKind,
+) -> Result<(Box<File>, Operation), CreateError> {
+    let target = dir.join(&name);
+    let error = |reason: String| CreateError {
+        path: target.clone(),
+        reason,
+    };
+
+    let relative = Path::new(&name);
+    let top_name = match relative.components().next() {
+        Some(Component::Normal(top)) if !relative.is_absolute() => top.to_owned(),
+        _ => return Err(error("must be a relative path".to_string())),
+    };
+
+    let mut created = dir.clone();
+    for component in relative.components() {
+        created.push(component);
+        match try_exists(&created).await {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => return Err(error(e.to_string())),
+        }
+    }
+
+    if created == target && try_exists(&target).await.unwrap_or(true) {
+        return Err(error("already exists".to_string()));
+    }
+
+    let result = async {
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent).await?;
+        }
+
+        match kind {
+            CreateKind::File => OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&target)
+                .await
+                .map(drop),
+            CreateKind::Directory => create_dir(&target).await,
+        }
+    }
+    .await;
+    result.map_err(|e| error(e.to_string()))?;
+
+    let top = dir.join(&top_name);
+    let metadata = symlink_metadata(&top)
+        .await
+        .map_err(|e| error(e.to_string()))?;
+    let file = File::new(top_name, 0, top, dir, metadata);
+
+    Ok((Box::new(file), Operation::Created(created)))
+}
+
+/// How many of `paths` already have a file with the same name in `dest`. Files already in
+/// `dest` don't count, a copy of them is renamed and a cut leaves them where they are without
+/// asking.
+pub async fn count_conflicts(paths: Vec<PathBuf>, dest: PathBuf) -> usize {
+    let mut conflicts = 0;
+
+    for path in paths {
+        if path.parent() == Some(dest.as_path()) {
+            continue;
+        }
+
+        if let Some(name) = path.file_name() {
+            if try_exists(dest.join(name)).await.unwrap_or(false) {
+                conflicts += 1;
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Copies or moves `paths` into `dest`, carrying on past the ones that fail.
+pub async fn paste(
+    paths: Vec<PathBuf>,
+    dest: PathBuf,
+    kind: RegisterKind,
+    policy: ConflictPolicy,
+    ctx: &mut JobContext,
+) -> (Vec<Operation>, Vec<PasteError>) {
+    let mut ops = vec![];
+    let mut errors = vec![];
+
+    for path in paths {
+        if let Err(e) = ctx.check() {
+            errors.push(PasteError {
+                path,
+                reason: e.to_string(),
+            });
+            break;
+        }
+
+        let result = paste_one(&path, &dest, kind, policy, ctx, &mut ops).await;
+        ctx.item_done();
+
+        if let Err(reason) = result {
+            errors.push(PasteError { path, reason });
+        }
+    }
+
+    (ops, errors)
+}
+
+async fn paste_one(
+    path: &Path,
+    dest: &Path,
+    kind: RegisterKind,
+    policy: ConflictPolicy,
+    ctx: &mut JobContext,
+    ops: &mut Vec<Operation>,
+) -> Result<(), String> {
+    let name = match path.file_name() {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+
+    let mut target = dest.join(name);
+
+    if dest.starts_with(path) && target != path {
+        return Err("cannot paste a directory into itself".to_string());
+    }
+
+    // A file pasted onto itself can only ever be a copy under a new name
+    let policy = match (target == path, kind) {
+        (true, RegisterKind::Cut) => return Ok(()),
+        (true, RegisterKind::Yank) => ConflictPolicy::Rename,
+        _ => policy,
+    };
+
+    if try_exists(&target).await.unwrap_or(false) {
+        match policy {
+            ConflictPolicy::Skip => return Ok(()),
+            ConflictPolicy::Rename => target = free_name(&target).await,
+            ConflictPolicy::Overwrite => match trash::trash(target.clone(), ctx).await {
+                Ok(entry) => ops.push(Operation::Trashed(entry)),
+                Err(e) => return Err(format!("{}: {e}", target.display())),
+            },
+        }
+    }
+
+    let result = match kind {
+        RegisterKind::Yank => copy_path(path, &target, ctx)
+            .await
+            .map(|_| Operation::Created(target.clone())),
+        RegisterKind::Cut => move_path(path, &target, ctx)
+            .await
+            .map(|_| Operation::Renamed {
+                from: path.to_path_buf(),
+                to: target.clone(),
+            }),
+    };
+
+    match result {
+        Ok(op) => {
+            ops.push(op);
+            Ok(())
+        }
+        Err(e) => {
+            // Nothing was at the target before, whatever a cancelled or failed copy left there
+            // goes. If it can't, it is journaled so undo can remove it. A failed move cleans up
+            // after itself.
+            let copied = kind == RegisterKind::Yank && try_exists(&target).await.unwrap_or(false);
+            if copied && remove_path(&target).await.is_err() {
+                ops.push(Operation::Created(target));
+            }
+
+            Err(e.to_string())
+        }
+    }
+}
+
+/// First of `name (1).ext`, `name (2).ext`, ... that doesn't exist yet.
+pub async fn free_name(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut n = 1;
+    loop {
+        let candidate = path.with_file_name(format!("{stem} ({n}){extension}"));
+        if !try_exists(&candidate).await.unwrap_or(true) {
+            return candidate;
+        }
+
+        n += 1;
+    }
+}
+
+/// Counts `paths` and everything below them, along with the bytes they take up. Anything that
+/// can't be read is left out of the count.
+pub async fn count_items(paths: Vec<PathBuf>) -> (usize, u64) {
+    let mut items = 0;
+    let mut bytes = 0;
+    let mut pending = paths;
+
+    while let Some(path) = pending.pop() {
+        let metadata = match symlink_metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        items += 1;
+
+        if metadata.is_dir() {
+            if let Ok(mut entries) = read_dir(&path).await {
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    pending.push(entry.path());
+                }
+            }
+        } else {
+            bytes += metadata.len();
+        }
+    }
+
+    (items, bytes)
+}
+
+/// Moves `from` to `to`, copying and removing the original when they live on different
+/// filesystems. A copy that fails or is cancelled is removed again.
+pub async fn move_path(from: &Path, to: &Path, ctx: &mut JobContext) -> io::Result<()> {
+    match rename(from, to).await {
+        // `rename` can't move across filesystems
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_path(from, to, ctx).await {
+                let _ = remove_path(to).await;
+                return Err(e);
+            }
+
+            remove_path(from).await
+        }
+        r => r,
+    }
+}
+
+/// Recursively copies `from` to `to`. Symlinks are copied as links, not followed.
+pub async fn copy_path(from: &Path, to: &Path, ctx: &mut JobContext) -> io::Result<()> {
+    let mut pending = vec![(from.to_path_buf(), to.to_path_buf())];
+
+    while let Some((from, to)) = pending.pop() {
+        ctx.check()?;
+
+        let metadata = symlink_metadata(&from).await?;
+
+        if metadata.is_dir() {
+            create_dir(&to).await?;
+
+            let mut entries = read_dir(&from).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                pending.push((entry.path(), to.join(entry.file_name())));
+            }
+        } else if metadata.file_type().is_symlink() {
+            copy_link(&from, &to).await?;
+        } else {
+            copy_file(&from, &to, ctx).await?;
+            set_permissions(&to, metadata.permissions()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies a single file in chunks, so progress can be reported and the copy cancelled midway.
+async fn copy_file(from: &Path, to: &Path, ctx: &mut JobContext) -> io::Result<()> {
+    let mut reader = FsFile::open(from).await?;
+    let mut writer = FsFile::create(to).await?;
+    let mut buf = vec![0; 64 * 1024];
 
     loop {
-        match dir.next_entry().await {
-            Ok(Some(f)) => files.push(File::new(
-                f.file_name().to_str().unwrap().to_string(),
-                0,
-                f.path(),
-                "ERROR".into(),
-                f.metadata().await.unwrap(),
-            )),
-            Ok(None) => break,
-            Err(e) => panic!("{}", e),
-        }
-    }
-
-    // for file in walk {
-    //     let file = file.unwrap();
-    //     let metadata = file.metadata().unwrap();
-    //     files.push(File::new(
-    //         file.file_name().to_os_string().into_string().unwrap(),
-    //         file.depth(),
-    //         file.path(),
-    //         file.parent_path().into(),
-    //         metadata.into(),
-    //     ));
-    // }
-
-    files
-
-    // tx.send(Message::NewFiles(files)).await.unwrap();
+        if let Err(e) = ctx.check() {
+            drop(writer);
+            let _ = remove_file(to).await;
+            return Err(e);
+        }
+
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+
+        writer.write_all(&buf[..n]).await?;
+        ctx.add_bytes(n as u64);
+    }
+
+    writer.flush().await
+}
+
+/// Copies a symlink as a symlink, pointing where the original does.
+async fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    tokio::fs::symlink(tokio::fs::read_link(from).await?, to).await
+}
+
+/// Removes a file, or a directory and everything in it.
+pub async fn remove_path(path: &Path) -> io::Result<()> {
+    if symlink_metadata(path).await?.is_dir() {
+        remove_dir_all(path).await
+    } else {
+        remove_file(path).await
+    }
 }
@@ -5,6 +5,8 @@ use iced::{
     Background, Color, Vector,
 };
 
+use crate::highlight::Highlight;
+
 #[allow(dead_code)]
 pub mod colors {
     use iced::Color;
@@ -132,6 +134,8 @@ pub enum ThemedText {
     Default,
     Hovered,
     Selected,
+    Error,
+    Highlight(Highlight),
 }
 
 impl iced_native::widget::text::StyleSheet for Theme {
@@ -142,6 +146,8 @@ impl iced_native::widget::text::StyleSheet for Theme {
             ThemedText::Default => colors::IVORY,
             ThemedText::Hovered => colors::LIGHT_GREEN,
             ThemedText::Selected => colors::L_NAVY,
+            ThemedText::Error => colors::SCALLOP_SEASHELL,
+            ThemedText::Highlight(kind) => self.highlight(kind),
         };
 
         iced_native::widget::text::Appearance { color: Some(color) }
@@ -226,6 +232,33 @@ impl application::StyleSheet for Theme {
     }
 }
 
+impl Theme {
+    /// Color of highlighted text in previews, derived from the primary and secondary colors so
+    /// it changes along with them.
+    pub fn highlight(&self, kind: Highlight) -> Color {
+        match kind {
+            Highlight::Plain => colors::IVORY,
+            Highlight::Comment => colors::LIGHT_GRAY,
+            Highlight::Keyword => mix(self.primary, Color::WHITE, 0.6),
+            Highlight::Function => mix(self.secondary, Color::WHITE, 0.6),
+            Highlight::Type => mix(self.secondary, colors::SKY_BLUE, 0.6),
+            Highlight::String => mix(self.primary, colors::SEAFOAM_GREEN, 0.7),
+            Highlight::Constant => mix(self.secondary, colors::ORANGE, 0.7),
+        }
+    }
+}
+
+/// Blends `a` into `b`, `amount` being how much of `b` there is in the result.
+fn mix(a: Color, b: Color, amount: f32) -> Color {
+    let blend = |a: f32, b: f32| a + (b - a) * amount;
+    Color::from_rgba(
+        blend(a.r, b.r),
+        blend(a.g, b.g),
+        blend(a.b, b.b),
+        blend(a.a, b.a),
+    )
+}
+
 pub trait RatioExt {
     fn to_color(&self) -> Color;
 }
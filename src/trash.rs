@@ -0,0 +1,340 @@
+//! Home trash following the freedesktop.org trash specification,
+//! <https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html>.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    io,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use tokio::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, try_exists, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use crate::{
+    dirs,
+    jobs::JobContext,
+    tasks::{move_path, remove_path},
+};
+
+const INFO_EXTENSION: &str = ".trashinfo";
+
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// Name of the file inside `Trash/files`, and of its `.trashinfo` without the extension.
+    pub name: OsString,
+    /// Where the file lived before it was trashed.
+    pub original: PathBuf,
+    /// `DeletionDate` as written in the info file, `YYYY-MM-DDThh:mm:ss` in local time.
+    pub deleted_at: String,
+}
+
+impl TrashEntry {
+    pub fn path(&self) -> PathBuf {
+        files_dir().join(&self.name)
+    }
+
+    fn info_path(&self) -> PathBuf {
+        info_dir().join(info_name(&self.name))
+    }
+}
+
+/// `$XDG_DATA_HOME/Trash`, defaulting to `~/.local/share/Trash`.
+pub fn trash_dir() -> PathBuf {
+    dirs::data_home().join("Trash")
+}
+
+fn files_dir() -> PathBuf {
+    trash_dir().join("files")
+}
+
+fn info_dir() -> PathBuf {
+    trash_dir().join("info")
+}
+
+/// Moves `path` into the trash, writing its `.trashinfo` first as the spec requires.
+pub async fn trash(path: PathBuf, ctx: &mut JobContext) -> io::Result<TrashEntry> {
+    let files = files_dir();
+    let info = info_dir();
+    create_dir_all(&files).await?;
+    create_dir_all(&info).await?;
+
+    let original = if path.is_absolute() {
+        path
+    } else {
+        env::current_dir()?.join(path)
+    };
+
+    let file_name = original
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash root"))?
+        .to_os_string();
+
+    let deleted_at = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={deleted_at}\n",
+        encode_path(&original)
+    );
+
+    // Creating the info file with O_EXCL is what reserves the name
+    let mut n = 1;
+    let (name, info_path) = loop {
+        let mut name = file_name.clone();
+        if n > 1 {
+            name.push(format!(".{n}"));
+        }
+        let info_path = info.join(info_name(&name));
+
+        if !try_exists(files.join(&name)).await? {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+                .await
+            {
+                Ok(mut f) => {
+                    f.write_all(contents.as_bytes()).await?;
+                    break (name, info_path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        n += 1;
+    };
+
+    if let Err(e) = move_path(&original, &files.join(&name), ctx).await {
+        let _ = remove_file(&info_path).await;
+        return Err(e);
+    }
+
+    Ok(TrashEntry {
+        name,
+        original,
+        deleted_at,
+    })
+}
+
+/// Reads every `.trashinfo` in the home trash. Malformed or unreadable entries are skipped.
+pub async fn list() -> io::Result<Vec<TrashEntry>> {
+    let mut entries = match read_dir(info_dir()).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+
+    let mut trashed = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        let name = match file_name.as_bytes().strip_suffix(INFO_EXTENSION.as_bytes()) {
+            Some(name) => OsStr::from_bytes(name).to_os_string(),
+            None => continue,
+        };
+
+        let contents = match read_to_string(entry.path()).await {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("couldn't read trash info {:?}: {e}", entry.path());
+                continue;
+            }
+        };
+
+        match parse_info(&contents) {
+            Some((original, deleted_at)) => trashed.push(TrashEntry {
+                name,
+                original,
+                deleted_at,
+            }),
+            None => warn!("malformed trash info {:?}", entry.path()),
+        }
+    }
+
+    trashed.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+
+    Ok(trashed)
+}
+
+/// Moves a trashed file back to where it came from. Refuses to overwrite anything living there
+/// now.
+pub async fn restore(entry: TrashEntry, ctx: &mut JobContext) -> io::Result<PathBuf> {
+    if try_exists(&entry.original).await? {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", entry.original.display()),
+        ));
+    }
+
+    if let Some(parent) = entry.original.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    move_path(&entry.path(), &entry.original, ctx).await?;
+    remove_file(entry.info_path()).await?;
+
+    Ok(entry.original)
+}
+
+/// Permanently deletes everything in the trash.
+pub async fn empty(ctx: &mut JobContext) -> io::Result<()> {
+    let entries = list().await?;
+    ctx.set_total(entries.len(), 0);
+
+    for entry in entries {
+        ctx.check()?;
+
+        let path = entry.path();
+        // Info files without a matching file are removed as well
+        if try_exists(&path).await? {
+            remove_path(&path).await?;
+        }
+
+        remove_file(entry.info_path()).await?;
+        ctx.item_done();
+    }
+
+    Ok(())
+}
+
+/// Name of the `.trashinfo` for a file trashed as `name`.
+fn info_name(name: &OsStr) -> OsString {
+    let mut info_name = name.to_os_string();
+    info_name.push(INFO_EXTENSION);
+    info_name
+}
+
+fn parse_info(contents: &str) -> Option<(PathBuf, String)> {
+    let mut lines = contents.lines().skip_while(|l| l.trim() != "[Trash Info]");
+    let _ = lines.next()?;
+
+    let mut path = None;
+    let mut deleted_at = String::new();
+    for line in lines {
+        if line.starts_with('[') {
+            break;
+        }
+
+        match line.split_once('=') {
+            Some(("Path", value)) => path = Some(decode_path(value)?),
+            Some(("DeletionDate", value)) => deleted_at = value.to_string(),
+            _ => (),
+        }
+    }
+
+    let path: PathBuf = path?;
+    // Relative paths are relative to the partition the trash lives on, which for the home trash
+    // is not something we can know, assume the root.
+    let path = if path.is_absolute() {
+        path
+    } else {
+        Path::new("/").join(path)
+    };
+
+    Some((path, deleted_at))
+}
+
+/// Percent-encodes a path as the spec asks for, leaving `/` and unreserved characters alone.
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+
+    encoded
+}
+
+fn decode_path(encoded: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    Some(OsString::from_vec(bytes).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_reserved_characters() {
+        assert_eq!(
+            encode_path(Path::new("/home/me/a file%.txt")),
+            "/home/me/a%20file%25.txt"
+        );
+        assert_eq!(encode_path(Path::new("/ä")), "/%C3%A4");
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        for path in ["/home/me/a file.txt", "/ä/ö", "/100%/~x_y-z.", "/"] {
+            let path = Path::new(path);
+            assert_eq!(decode_path(&encode_path(path)).as_deref(), Some(path));
+        }
+    }
+
+    #[test]
+    fn non_utf8_paths_round_trip() {
+        let path = PathBuf::from(OsString::from_vec(b"/tmp/caf\xe9".to_vec()));
+        assert_eq!(encode_path(&path), "/tmp/caf%E9");
+        assert_eq!(decode_path(&encode_path(&path)), Some(path));
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        assert_eq!(decode_path("/a%2"), None);
+        assert_eq!(decode_path("/a%zz"), None);
+    }
+
+    #[test]
+    fn parses_info() {
+        let info = "[Trash Info]\nPath=/home/me/a%20b\nDeletionDate=2022-05-01T10:00:00\n";
+        assert_eq!(
+            parse_info(info),
+            Some((
+                PathBuf::from("/home/me/a b"),
+                "2022-05-01T10:00:00".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn info_ignores_other_groups_and_keys() {
+        let info =
+            "[Other]\nPath=/wrong\n[Trash Info]\nFoo=bar\nPath=/right\n[Next]\nPath=/wrong\n";
+        assert_eq!(
+            parse_info(info),
+            Some((PathBuf::from("/right"), String::new()))
+        );
+    }
+
+    #[test]
+    fn relative_info_paths_are_from_the_root() {
+        let info = "[Trash Info]\nPath=a/b\nDeletionDate=2022-05-01T10:00:00\n";
+        assert_eq!(
+            parse_info(info).map(|(p, _)| p),
+            Some(PathBuf::from("/a/b"))
+        );
+    }
+
+    #[test]
+    fn info_without_path_is_malformed() {
+        assert_eq!(parse_info("[Trash Info]\nDeletionDate=2022\n"), None);
+        assert_eq!(parse_info("Path=/a\n"), None);
+    }
+}
@@ -2,54 +2,302 @@ use iced::{
     container::StyleSheet,
     pure::{
         text,
-        widget::{Button, Column, Container, Row, Scrollable},
+        widget::{Button, Column, Container, Image, Row, Scrollable},
         Element,
     },
-    Length, Padding, Space,
+    Alignment, Length, Padding, Space,
 };
 
 use crate::{
-    app::{Fls, Message, View},
-    mode::Mode,
+    app::{Action, File, Fls, ListError, Message, RegisterKind, View},
+    details::{self, Detail},
+    input::LineInput,
+    jobs::{Job, Progress},
+    keymap::{Keymap, Section},
+    mode::{Mode, SearchMode},
+    notification::Severity,
+    preview::Preview,
+    tasks::{ConflictPolicy, CreateKind},
     theme::{colors, ContainerKind, Theme, ThemedButton, ThemedText},
 };
 
-use self::settings::draw_settings;
+use self::{bookmarks::draw_bookmarks, settings::draw_settings, trash::draw_trash};
 
 pub fn draw(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
-    match &app.curr_view {
+    let view = match &app.curr_view {
         View::MainView => draw_main(app),
         View::Settings(s) => draw_settings(s, app),
+        View::Trash(t) => draw_trash(t, app),
+        View::Bookmarks(b) => draw_bookmarks(b, app),
+    };
+
+    Column::new()
+        .push(draw_notifications(app))
+        .push(view)
+        .into()
+}
+
+/// Notifications stacked on top of whatever view is open, newest first.
+pub fn draw_notifications(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
+    const MAX_SHOWN: usize = 3;
+
+    let mut col = Column::new();
+    for notification in app.notifications.iter().rev().take(MAX_SHOWN) {
+        let color = match notification.severity {
+            Severity::Info => colors::DARK_BLUE,
+            Severity::Warning => colors::SAGE,
+            Severity::Error => colors::RED,
+        };
+
+        let dismiss = Button::new(text("x"))
+            .on_press(Message::DismissNotification(notification.id))
+            .style(ThemedButton::Default);
+
+        let row = Row::new()
+            .align_items(Alignment::Center)
+            .push(text(&notification.message).width(Length::Fill))
+            .push(dismiss);
+
+        col = col.push(
+            Container::new(row)
+                .width(Length::Fill)
+                .padding(Padding::custom(4, 10, 4, 10))
+                .style(ContainerKind::Color(color)),
+        );
     }
+
+    col.into()
 }
 
 pub fn draw_main(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
     Column::new()
+        .push(draw_tabs(app))
         .push(draw_status(app))
-        .push(draw_files(app))
+        .push(draw_banner(app))
+        .push(draw_panes(app))
         .push(draw_search(app))
         .into()
 }
 
+/// Numbered tabs, the shown one highlighted. Left out while there is only one.
+pub fn draw_tabs(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
+    if app.tab_count() == 1 {
+        return Space::new(Length::Fill, Length::Units(0)).into();
+    }
+
+    let row = app
+        .tabs()
+        .enumerate()
+        .fold(Row::new().spacing(20), |row, (idx, tab)| {
+            let style = if idx == app.active_tab {
+                ThemedText::Hovered
+            } else {
+                Default::default()
+            };
+
+            row.push(text(format!("{} {}", idx + 1, tab.title())).style(style))
+        });
+
+    Container::new(row)
+        .width(Length::Fill)
+        .padding(Padding::custom(8, 10, 0, 10))
+        .into()
+}
+
+/// What couldn't be read while listing the current directory. Unlike the toasts, it stays up
+/// until the directory is listed again.
+pub fn draw_banner(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
+    let ListError { path, reason } = match app.tab.list_errors.first() {
+        Some(error) => error,
+        None => return Space::new(Length::Fill, Length::Units(0)).into(),
+    };
+
+    let mut message = if *path == app.tab.current_dir {
+        format!("Can't open {}: {reason}", path.display())
+    } else {
+        format!("Couldn't read {}: {reason}", path.display())
+    };
+
+    if app.tab.list_errors.len() > 1 {
+        message.push_str(&format!(" and {} more", app.tab.list_errors.len() - 1));
+    }
+
+    Container::new(text(message).style(ThemedText::Error))
+        .width(Length::Fill)
+        .padding(Padding::custom(8, 10, 8, 10))
+        .style(ContainerKind::Color(colors::RED))
+        .into()
+}
+
 pub fn draw_status(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
-    Container::new(
-        Row::new()
-            .width(Length::Fill)
-            .padding(Padding::left(10))
-            .push(text(app.current_dir.to_str().unwrap_or("Unknown"))),
-    )
-    .width(Length::Fill)
-    .height(Length::Units(50))
-    .center_x()
-    .center_y()
-    .into()
+    let mut row = Row::new()
+        .width(Length::Fill)
+        .padding(Padding::custom(0, 10, 0, 10))
+        .push(text(app.tab.current_dir.to_str().unwrap_or("Unknown")).width(Length::Fill));
+
+    if app.filter.show_hidden {
+        row = row.push(text("hidden shown  "));
+    }
+    if app.filter.respect_ignore {
+        row = row.push(text(".gitignore respected  "));
+    }
+
+    let (sort, remembered) = app.sort();
+    let here = if remembered { " (this directory)" } else { "" };
+    row = row.push(text(format!("{sort}{here}  ")));
+
+    if let Some(register) = &app.register {
+        let verb = match register.kind {
+            RegisterKind::Yank => "yanked",
+            RegisterKind::Cut => "cut",
+        };
+
+        row = row.push(text(format!("{} {verb}  ", register.paths.len())));
+    }
+
+    // Keys typed so far of a sequence or count
+    let pending = app.pending_keys.to_string();
+    if !pending.is_empty() {
+        row = row.push(text(format!("{pending}  ")));
+    }
+
+    for job in &app.jobs {
+        row = row.push(text(format!("{}  ", job_status(job))));
+    }
+
+    Container::new(row)
+        .width(Length::Fill)
+        .height(Length::Units(50))
+        .center_x()
+        .center_y()
+        .into()
+}
+
+/// Something like `Copying 3/10 [====      ] 1.2 MB/4.0 MB`.
+fn job_status(job: &Job) -> String {
+    const BAR_WIDTH: usize = 10;
+
+    let Progress {
+        items_done,
+        items_total,
+        bytes_done,
+        bytes_total,
+    } = job.progress;
+
+    let mut status = format!("{} {items_done}/{items_total}", job.kind.description());
+
+    // Bytes say more about how far along a copy is than the amount of top level items do
+    let ratio = if bytes_total > 0 {
+        bytes_done as f32 / bytes_total as f32
+    } else if items_total > 0 {
+        items_done as f32 / items_total as f32
+    } else {
+        0.0
+    };
+    let filled = ((ratio.min(1.0) * BAR_WIDTH as f32) as usize).min(BAR_WIDTH);
+    status.push_str(&format!(
+        " [{}{}]",
+        "=".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled)
+    ));
+
+    if bytes_total > 0 {
+        status.push_str(&format!(
+            " {}/{}",
+            human_size(bytes_done),
+            human_size(bytes_total)
+        ));
+    }
+
+    if job.is_cancelled() {
+        status.push_str(" cancelling");
+    }
+
+    status
+}
+
+/// The file list, with the preview of the hovered file next to it if that is turned on.
+pub fn draw_panes(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
+    let row = Row::new().push(draw_files(app));
+
+    if app.show_preview {
+        row.push(draw_preview(app)).into()
+    } else {
+        row.into()
+    }
+}
+
+pub fn draw_preview(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
+    let hovered = app.files().nth(app.tab.hovered).map(|f| &f.data.path);
+
+    let content: Element<'_, Message, iced::Renderer<Theme>> = match (hovered, &app.preview) {
+        (Some(hovered), Some((path, preview))) if hovered == path => match preview {
+            Preview::Text(lines) => lines
+                .iter()
+                .fold(Column::new(), |col, line| {
+                    // An empty row would take no space at all
+                    let row = line.iter().fold(Row::new(), |row, (kind, span)| {
+                        row.push(text(span).size(16).style(ThemedText::Highlight(*kind)))
+                    });
+                    col.push(if line.is_empty() {
+                        row.push(text(" ").size(16))
+                    } else {
+                        row
+                    })
+                })
+                .into(),
+            Preview::Hexdump(dump) => text(dump).size(16).into(),
+            Preview::Directory(names) => names
+                .iter()
+                .fold(Column::new(), |col, name| col.push(text(name).size(16)))
+                .into(),
+            Preview::Image(handle) => Image::new(handle.clone()).width(Length::Fill).into(),
+            Preview::Error(e) => text(e).style(ThemedText::Error).into(),
+        },
+        (Some(_), _) => text("Loading...").into(),
+        (None, _) => Space::new(Length::Fill, Length::Shrink).into(),
+    };
+
+    draw_list(Column::new().push(content), app)
+}
+
+/// A file with its detail columns, the symlink target follows the name.
+fn draw_long_row<'a>(
+    app: &'a Fls,
+    file: &File,
+    name: String,
+    style: ThemedText,
+) -> Element<'a, Message, iced::Renderer<Theme>> {
+    let mut name = name;
+    let mut row = Row::new().spacing(16);
+
+    for detail in &app.details {
+        let width = match detail {
+            Detail::Permissions => 110,
+            Detail::Owner => 140,
+            Detail::Size => 90,
+            Detail::Modified => 160,
+            Detail::Target => {
+                name.push_str(&details::format(*detail, file, &app.owners));
+                continue;
+            }
+        };
+
+        row = row.push(
+            text(details::format(*detail, file, &app.owners))
+                .style(style)
+                .width(Length::Units(width)),
+        );
+    }
+
+    row.push(text(name).style(style).width(Length::Fill)).into()
 }
 
 pub fn draw_files(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
     let mut col = Column::new();
 
     for (idx, file) in app.files().enumerate() {
-        let style = if idx == app.hovered {
+        let style = if idx == app.tab.hovered {
             ThemedText::Hovered
         } else if file.selected {
             ThemedText::Selected
@@ -58,12 +306,47 @@ pub fn draw_files(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
         };
 
         let after = if file.data.metadata.is_dir() { "/" } else { "" };
+        let name = if file.data.depth > 0 {
+            file.data
+                .relative_path(&app.tab.current_dir)
+                .to_string_lossy()
+        } else {
+            file.data.display_name()
+        };
+
+        let row: Element<'_, Message, iced::Renderer<Theme>> =
+            if idx == app.tab.hovered && app.tab.mode == Mode::Rename {
+                draw_input(&app.input)
+            } else if app.long_listing {
+                draw_long_row(app, &file.data, format!("{name}{after}"), style)
+            } else {
+                text(format!("{name}{after}")).style(style).into()
+            };
 
         col = col
-            .push(text(format!("{}{after}", &file.data.name)).style(style))
+            .push(row)
             .push(Space::new(Length::Fill, Length::Units(3)));
     }
 
+    draw_list(col, app)
+}
+
+/// Text being edited, with a bar where the cursor is.
+pub fn draw_input(input: &LineInput) -> Element<'_, Message, iced::Renderer<Theme>> {
+    let (before, after) = input.split();
+
+    Row::new()
+        .push(text(before).style(ThemedText::Hovered))
+        .push(text("|").style(ThemedText::Selected))
+        .push(text(after).style(ThemedText::Hovered))
+        .into()
+}
+
+/// Bordered, scrollable box used for the file list and other lists shaped like it.
+pub fn draw_list<'a>(
+    col: Column<'a, Message, iced::Renderer<Theme>>,
+    app: &Fls,
+) -> Element<'a, Message, iced::Renderer<Theme>> {
     Container::new(
         Container::new(Scrollable::new(col))
             .height(Length::Fill)
@@ -84,20 +367,89 @@ pub fn draw_files(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
 }
 
 pub fn draw_search(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
-    let is_search = match app.mode {
-        Mode::Search(_) => true,
-        _ => false,
+    let is_search = !matches!(app.tab.mode, Mode::Normal);
+
+    let pre = match app.tab.mode {
+        Mode::Search(SearchMode::Global(depth)) => format!("{depth}>"),
+        Mode::Search(SearchMode::Regular) => ">".to_string(),
+        _ => String::new(),
     };
 
-    let pre = if is_search { ">" } else { "" };
+    let content = match app.tab.mode {
+        Mode::ConfirmDelete { items, bytes } => {
+            let keys = key_hints(
+                &app.keymap,
+                Section::ConfirmDelete,
+                &[
+                    (|a| matches!(a, Action::ConfirmDelete), "delete"),
+                    (|a| matches!(a, Action::CancelDelete), "cancel"),
+                ],
+            );
+            format!(
+                "Delete {items} items ({})? {}",
+                human_size(bytes),
+                keys.join(", ")
+            )
+        }
+        Mode::ResolveConflict { conflicts } => {
+            let keys = key_hints(
+                &app.keymap,
+                Section::ResolveConflict,
+                &[
+                    (
+                        |a| matches!(a, Action::PasteWith(ConflictPolicy::Overwrite)),
+                        "overwrite",
+                    ),
+                    (
+                        |a| matches!(a, Action::PasteWith(ConflictPolicy::Skip)),
+                        "skip",
+                    ),
+                    (
+                        |a| matches!(a, Action::PasteWith(ConflictPolicy::Rename)),
+                        "rename",
+                    ),
+                ],
+            );
+            format!("{conflicts} files already exist: {}", keys.join(", "))
+        }
+        Mode::Rename => "Rename: enter to confirm, esc to cancel".to_string(),
+        Mode::SetMark => "Mark the current directory as: (a letter)".to_string(),
+        Mode::GoToMark => "Go to mark: (a letter, ' to go back)".to_string(),
+        Mode::Visual { .. } => {
+            let selected = app.files().filter(|f| f.selected).count();
+            format!("-- VISUAL -- {selected} selected")
+        }
+        Mode::Command => {
+            let (before, after) = app.input.split();
+            format!(":{before}|{after}")
+        }
+        Mode::Jump => {
+            let (before, after) = app.input.split();
+            let matches = app.frecency.matching(&app.input.text);
+            let best = match matches.iter().find(|v| v.dir != app.tab.current_dir) {
+                _ if app.frecency.is_empty() => "no directories visited yet".to_string(),
+                Some(visits) => visits.dir.to_string_lossy().into_owned(),
+                None => "no match".to_string(),
+            };
+
+            format!("Jump: {before}|{after}  -> {best}")
+        }
+        Mode::Create(kind) => {
+            let what = match kind {
+                CreateKind::File => "file",
+                CreateKind::Directory => "directory",
+            };
+            let (before, after) = app.input.split();
 
-    let button = Button::new(
-        text(format!("{pre} {}", &app.search_term))
-            .vertical_alignment(iced::alignment::Vertical::Center),
-    )
-    .width(Length::Units(u16::MAX))
-    .height(Length::Units(37))
-    .style(ThemedButton::Search(is_search));
+            format!("New {what}: {before}|{after}")
+        }
+        _ => format!("{pre} {}", &app.tab.search_term),
+    };
+
+    let button = Button::new(text(content).vertical_alignment(iced::alignment::Vertical::Center))
+        .width(Length::Units(u16::MAX))
+        .height(Length::Units(37))
+        .style(ThemedButton::Search(is_search));
 
     Container::new(button)
         .padding(Padding::custom(0, 8, 8, 8))
@@ -107,6 +459,39 @@ pub fn draw_search(app: &Fls) -> Element<'_, Message, iced::Renderer<Theme>> {
         .into()
 }
 
+/// Picks out an action, along with what to call it in a hint.
+pub type KeyHint<'a> = (fn(&Action) -> bool, &'a str);
+
+/// `key: what it does` for every action in `actions` that has a key in `section`. Actions the
+/// user unbound are left out.
+pub fn key_hints(keymap: &Keymap, section: Section, actions: &[KeyHint<'_>]) -> Vec<String> {
+    actions
+        .iter()
+        .filter_map(|(is_action, what)| {
+            let keys = keymap.keys_for(section, is_action)?;
+            Some(format!("{keys}: {what}"))
+        })
+        .collect()
+}
+
+/// Formats a byte count with binary prefixes, e.g. `4.2 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
 pub mod components {
     use iced::{
         pure::{
@@ -220,6 +605,161 @@ pub mod settings {
     }
 }
 
+pub mod trash {
+    use iced::{
+        pure::{
+            text,
+            widget::{Column, Container, Row},
+            Element,
+        },
+        Length, Padding, Space,
+    };
+
+    use crate::{
+        app::{Action, Fls, Message, TrashView, View},
+        keymap::Section,
+        theme::{Theme, ThemedText},
+    };
+
+    use super::{draw_list, key_hints, PaddingExt};
+
+    pub fn draw_trash<'a>(
+        t: &'a TrashView,
+        fls: &'a Fls,
+    ) -> Element<'a, Message, iced::Renderer<Theme>> {
+        let status = Container::new(
+            Row::new()
+                .width(Length::Fill)
+                .padding(Padding::left(10))
+                .push(text(format!("Trash ({} items)", t.entries.len())).width(Length::Fill))
+                .push(text(
+                    key_hints(
+                        &fls.keymap,
+                        Section::Trash,
+                        &[
+                            (|a| matches!(a, Action::RestoreTrashed), "restore"),
+                            (|a| matches!(a, Action::EmptyTrash), "empty"),
+                            (|a| matches!(a, Action::NewView(View::MainView)), "back"),
+                        ],
+                    )
+                    .join("  "),
+                )),
+        )
+        .width(Length::Fill)
+        .height(Length::Units(50))
+        .center_y();
+
+        let mut col = Column::new();
+
+        for (idx, entry) in t.entries.iter().enumerate() {
+            let style = if idx == t.hovered {
+                ThemedText::Hovered
+            } else {
+                Default::default()
+            };
+
+            col = col
+                .push(
+                    Row::new()
+                        .push(
+                            text(entry.original.to_string_lossy())
+                                .style(style)
+                                .width(Length::Fill),
+                        )
+                        .push(text(&entry.deleted_at).style(style)),
+                )
+                .push(Space::new(Length::Fill, Length::Units(3)));
+        }
+
+        if t.entries.is_empty() {
+            col = col.push(text("Trash is empty"));
+        }
+
+        Column::new().push(status).push(draw_list(col, fls)).into()
+    }
+}
+
+pub mod bookmarks {
+    use iced::{
+        pure::{
+            text,
+            widget::{Column, Container, Row},
+            Element,
+        },
+        Length, Padding, Space,
+    };
+
+    use crate::{
+        app::{BookmarksView, Fls, Message},
+        theme::{Theme, ThemedText},
+    };
+
+    use super::{draw_input, draw_list, PaddingExt};
+
+    pub fn draw_bookmarks<'a>(
+        b: &'a BookmarksView,
+        fls: &'a Fls,
+    ) -> Element<'a, Message, iced::Renderer<Theme>> {
+        let status = Container::new(
+            Row::new()
+                .width(Length::Fill)
+                .padding(Padding::left(10))
+                .push(text("Bookmarks: ").width(Length::Shrink))
+                .push(Container::new(draw_input(&b.query)).width(Length::Fill))
+                .push(text(
+                    "enter: jump  ctrl+r: rename  ctrl+d: delete  esc: back",
+                )),
+        )
+        .width(Length::Fill)
+        .height(Length::Units(50))
+        .center_y();
+
+        let marks = fls.marks.matching(&b.query.text);
+        let mut col = Column::new();
+
+        for (idx, mark) in marks.iter().enumerate() {
+            let style = if idx == b.hovered {
+                ThemedText::Hovered
+            } else {
+                Default::default()
+            };
+
+            let name: Element<'a, Message, iced::Renderer<Theme>> = match &b.renaming {
+                Some((letter, input)) if *letter == mark.letter => draw_input(input),
+                _ => text(&mark.name).style(style).into(),
+            };
+
+            col = col
+                .push(
+                    Row::new()
+                        .push(
+                            text(mark.letter)
+                                .style(ThemedText::Selected)
+                                .width(Length::Units(30)),
+                        )
+                        .push(Container::new(name).width(Length::Units(240)))
+                        .push(
+                            text(mark.dir.to_string_lossy())
+                                .style(style)
+                                .width(Length::Fill),
+                        ),
+                )
+                .push(Space::new(Length::Fill, Length::Units(3)));
+        }
+
+        if marks.is_empty() {
+            let empty = if b.query.text.is_empty() {
+                "No marks yet, m followed by a letter marks the current directory"
+            } else {
+                "No marks match"
+            };
+            col = col.push(text(empty));
+        }
+
+        Column::new().push(status).push(draw_list(col, fls)).into()
+    }
+}
+
 pub trait PaddingExt {
     fn left(padding: u16) -> Padding {
         Padding::from([0, 0, 0, padding])
@@ -0,0 +1,158 @@
+//! Keeps the listing of the current directory up to date while other programs change it.
+
+use std::{
+    any::TypeId,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::{futures::future::pending, Subscription};
+use iced_native::subscription::unfold;
+use log::warn;
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{
+    fs::{read_link, symlink_metadata},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    time::sleep,
+};
+
+use crate::{
+    app::{File, Message},
+    tasks::{DirFilter, Filter},
+};
+
+/// Files that change what the listing leaves out, read again when they change.
+const FILTER_FILES: [&str; 3] = [".ignore", ".gitignore", ".git"];
+
+/// How long to wait for more events after one comes in, so a burst is applied at once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// A file in the watched directory that appeared, changed or went away.
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// Created or modified, with what it looks like now.
+    Changed(Box<File>),
+    Removed(PathBuf),
+}
+
+/// Watches the files directly in `dir`, sending a `Watched` message for every burst of changes.
+/// Files the listing leaves out with `filter` are left out here as well.
+pub fn watch(dir: PathBuf, filter: Filter) -> Subscription<Message> {
+    struct DirWatcher;
+
+    unfold(
+        (TypeId::of::<DirWatcher>(), dir.clone(), filter),
+        State::Start,
+        move |state| {
+            let dir = dir.clone();
+            async move {
+                match state {
+                    State::Start => match start(&dir) {
+                        Ok((watcher, rx)) => {
+                            let dir_filter = DirFilter::read(dir.clone(), filter).await;
+                            (None, State::Watching(watcher, rx, dir_filter))
+                        }
+                        Err(e) => {
+                            warn!("not watching {dir:?}: {e}");
+                            (None, State::Stopped)
+                        }
+                    },
+                    State::Watching(watcher, mut rx, mut dir_filter) => {
+                        let mut paths = match rx.recv().await {
+                            Some(event) => event_paths(event),
+                            None => return (None, State::Stopped),
+                        };
+
+                        sleep(DEBOUNCE).await;
+                        while let Ok(event) = rx.try_recv() {
+                            paths.extend(event_paths(event));
+                        }
+
+                        // Only direct children are listed, `dir` itself may show up too
+                        paths.retain(|p| p.parent() == Some(dir.as_path()));
+                        paths.sort();
+                        paths.dedup();
+
+                        let filter_changed = paths.iter().any(|p| {
+                            p.file_name()
+                                .is_some_and(|name| FILTER_FILES.iter().any(|f| name == *f))
+                        });
+                        if filter_changed {
+                            dir_filter = DirFilter::read(dir.clone(), filter).await;
+                        }
+
+                        let mut changes = vec![];
+                        for path in paths {
+                            changes.extend(change(&dir, path, &dir_filter).await);
+                        }
+
+                        let message =
+                            (!changes.is_empty()).then(|| Message::Watched(dir.clone(), changes));
+                        (message, State::Watching(watcher, rx, dir_filter))
+                    }
+                    State::Stopped => pending().await,
+                }
+            }
+        },
+    )
+}
+
+enum State {
+    Start,
+    /// The watcher stops watching once dropped, so it lives as long as the subscription.
+    Watching(
+        RecommendedWatcher,
+        UnboundedReceiver<notify::Result<Event>>,
+        DirFilter,
+    ),
+    Stopped,
+}
+
+fn start(
+    dir: &Path,
+) -> notify::Result<(RecommendedWatcher, UnboundedReceiver<notify::Result<Event>>)> {
+    let (tx, rx) = unbounded_channel();
+    let mut watcher = recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    Ok((watcher, rx))
+}
+
+fn event_paths(event: notify::Result<Event>) -> Vec<PathBuf> {
+    match event {
+        Ok(event) => event.paths,
+        Err(e) => {
+            warn!("watch error: {e}");
+            vec![]
+        }
+    }
+}
+
+/// Looks at what is at `path` now. Renames are seen as the old path going away and the new one
+/// showing up.
+async fn change(dir: &Path, path: PathBuf, dir_filter: &DirFilter) -> Option<Change> {
+    match symlink_metadata(&path).await {
+        Ok(metadata) => {
+            if dir_filter.is_filtered(&path, metadata.is_dir()) {
+                return Some(Change::Removed(path));
+            }
+
+            let name = path.file_name()?.to_os_string();
+            let is_symlink = metadata.file_type().is_symlink();
+            let mut file = File::new(name, 0, path, dir.to_path_buf(), metadata);
+            if is_symlink {
+                file.link_target = read_link(&file.path).await.ok();
+            }
+
+            Some(Change::Changed(Box::new(file)))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Change::Removed(path)),
+        Err(e) => {
+            warn!("can't look at {path:?}: {e}");
+            None
+        }
+    }
+}